@@ -0,0 +1,177 @@
+// src/codes/mod.rs
+// Códigos con dígito verificador (Luhn mod N sobre base 36).
+//
+// Formato nuevo: NOMBRE-UNIQUEID-NUM-X, donde X es el carácter verificador.
+// Así podemos descartar typos y escaneos "a lo loco" sin tocar Mongo.
+
+const ALFABETO: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const N: u32 = ALFABETO.len() as u32;
+
+// Largo máximo razonable de un código (evita que nos manden basura gigante)
+const MAX_LARGO: usize = 64;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Formato {
+    /// Código nuevo con verificador correcto
+    ConVerificador,
+    /// Código viejo (generado antes del verificador). Solo se valida la forma.
+    Legacy,
+    /// Basura, typo o verificador incorrecto: ni siquiera vamos a la BD
+    Invalido,
+}
+
+fn valor(c: char) -> Option<u32> {
+    ALFABETO.iter().position(|&a| a as char == c).map(|p| p as u32)
+}
+
+/// Calcula el carácter verificador Luhn mod 36 de `payload`.
+/// Los guiones (y cualquier carácter fuera del alfabeto) se ignoran.
+pub fn verificador(payload: &str) -> char {
+    let mut factor = 2;
+    let mut suma = 0;
+
+    // Recorremos de derecha a izquierda, igual que Luhn clásico
+    for c in payload.chars().rev() {
+        let Some(v) = valor(c) else { continue };
+        let addend = factor * v;
+        suma += (addend / N) + (addend % N);
+        factor = if factor == 2 { 1 } else { 2 };
+    }
+
+    let resto = suma % N;
+    ALFABETO[((N - resto) % N) as usize] as char
+}
+
+/// Agrega el verificador al final: "HUASTECA-748291-0001" -> "HUASTECA-748291-0001-K"
+pub fn con_verificador(payload: &str) -> String {
+    format!("{}-{}", payload, verificador(payload))
+}
+
+/// Clasifica un código recibido en /v/:codigo sin consultar la BD.
+pub fn clasificar(codigo: &str) -> Formato {
+    if codigo.is_empty() || codigo.len() > MAX_LARGO {
+        return Formato::Invalido;
+    }
+
+    // Cualquier cosa fuera de letras, números, guion o guion bajo no es un código nuestro
+    if !codigo.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+        return Formato::Invalido;
+    }
+
+    // Formato nuevo: termina en "-X" (un solo carácter) y todo lo demás es base 36
+    if let Some((payload, check)) = codigo.rsplit_once('-')
+        && let &[check] = check.as_bytes()
+        && codigo.chars().all(|c| c == '-' || valor(c).is_some())
+    {
+        return if check as char == verificador(payload) {
+            Formato::ConVerificador
+        } else {
+            Formato::Invalido
+        };
+    }
+
+    Formato::Legacy
+}
+
+/// Los imanes ya grabados con el formato viejo siguen funcionando salvo que
+/// se apague explícitamente con PERMITIR_CODIGOS_LEGACY=false
+pub fn permitir_legacy() -> bool {
+    std::env::var("PERMITIR_CODIGOS_LEGACY")
+        .map(|v| v != "false" && v != "0")
+        .unwrap_or(true)
+}
+
+/// Atajo para el handler: ¿vale la pena buscar este código en Mongo?
+pub fn es_valido(codigo: &str) -> bool {
+    match clasificar(codigo) {
+        Formato::ConVerificador => true,
+        Formato::Legacy => permitir_legacy(),
+        Formato::Invalido => false,
+    }
+}
+
+/// Convierte el nombre del lote en la parte inicial del código:
+/// "Peña Huasteca" -> "PENAHUASTECA". Solo quedan A-Z y 0-9.
+pub fn slug_lote(nombre: &str) -> String {
+    nombre
+        .chars()
+        .map(|c| match c {
+            'á' | 'à' | 'ä' | 'â' | 'Á' | 'À' | 'Ä' | 'Â' => 'A',
+            'é' | 'è' | 'ë' | 'ê' | 'É' | 'È' | 'Ë' | 'Ê' => 'E',
+            'í' | 'ì' | 'ï' | 'î' | 'Í' | 'Ì' | 'Ï' | 'Î' => 'I',
+            'ó' | 'ò' | 'ö' | 'ô' | 'Ó' | 'Ò' | 'Ö' | 'Ô' => 'O',
+            'ú' | 'ù' | 'ü' | 'û' | 'Ú' | 'Ù' | 'Ü' | 'Û' => 'U',
+            'ñ' | 'Ñ' => 'N',
+            otro => otro.to_ascii_uppercase(),
+        })
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect()
+}
+
+/// Mismo nombre de lote con otro id al azar: "HUASTECA-748291" -> "HUASTECA-305117".
+/// Para cuando el prefijo de un lote choca con los códigos de otro.
+pub fn otro_prefijo(prefijo: &str) -> String {
+    let nombre = prefijo.rsplit_once('-').map(|(n, _)| n).unwrap_or(prefijo);
+    format!("{}-{}", nombre, rand::random_range(100_000..1_000_000))
+}
+
+/// Número consecutivo dentro del lote: "HUASTECA-748291-0042-K" -> 42.
+/// También funciona con códigos legacy ("HUASTECA-748291-0042").
+pub fn secuencia(codigo: &str) -> Option<u32> {
+    let sin_check = match clasificar(codigo) {
+        Formato::ConVerificador => codigo.rsplit_once('-').map(|(p, _)| p)?,
+        _ => codigo,
+    };
+    sin_check.rsplit('-').next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vector_conocido() {
+        assert_eq!(verificador("A1"), 'O');
+        assert_eq!(con_verificador("HUASTECA-748291-0001"), "HUASTECA-748291-0001-E");
+        assert_eq!(clasificar("HUASTECA-748291-0001-E"), Formato::ConVerificador);
+        assert_eq!(secuencia("HUASTECA-748291-0001-E"), Some(1));
+    }
+
+    #[test]
+    fn un_caracter_cambiado_es_invalido() {
+        let codigo = con_verificador("HUASTECA-748291-0001");
+        for (i, original) in codigo.char_indices() {
+            if original == '-' {
+                continue;
+            }
+            for &otro in ALFABETO {
+                let otro = otro as char;
+                if otro == original {
+                    continue;
+                }
+                let mut typo = codigo.clone();
+                typo.replace_range(i..i + 1, &otro.to_string());
+                assert_eq!(clasificar(&typo), Formato::Invalido, "{}", typo);
+            }
+        }
+    }
+
+    #[test]
+    fn otro_prefijo_conserva_el_nombre() {
+        let nuevo = otro_prefijo("HUASTECA-748291");
+        let (nombre, id) = nuevo.rsplit_once('-').unwrap();
+        assert_eq!(nombre, "HUASTECA");
+        assert_eq!(id.len(), 6);
+        assert!(id.chars().all(|c| c.is_ascii_digit()));
+
+        let codigo = con_verificador(&format!("{}-{:04}", nuevo, 7));
+        assert_eq!(clasificar(&codigo), Formato::ConVerificador);
+        assert_eq!(secuencia(&codigo), Some(7));
+    }
+
+    #[test]
+    fn verificador_de_mas_de_un_caracter_no_es_formato_nuevo() {
+        assert_eq!(clasificar("HUASTECA-748291-0001-EE"), Formato::Legacy);
+        assert_eq!(clasificar("HUASTECA-748291-0001-"), Formato::Legacy);
+    }
+}
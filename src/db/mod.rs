@@ -1,5 +1,10 @@
 // src/db/mod.rs
-use mongodb::{options::ClientOptions, Client, Database};
+use mongodb::{
+    bson::doc,
+    error::{Error, ErrorKind, WriteFailure},
+    options::{ClientOptions, IndexOptions},
+    Client, Database, IndexModel,
+};
 use std::env;
 use std::sync::Arc;
 
@@ -26,5 +31,145 @@ pub async fn init_db() -> Result<DB, mongodb::error::Error> {
 
     println!("✅ Conexión a MongoDB exitosa");
 
+    crear_indices(&db).await?;
+
     Ok(Arc::new(db))
 }
+
+// Índices que la app necesita para funcionar bien (create_index es idempotente
+// mientras no cambien las opciones; el TTL sí puede cambiar, ver abajo)
+async fn crear_indices(db: &Database) -> Result<(), Error> {
+    // Claves de idempotencia: únicas y con caducidad automática (TTL)
+    let ventana_horas: u64 = env::var("IDEMPOTENCIA_VENTANA_HORAS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(24);
+
+    let idempotencia = db.collection::<mongodb::bson::Document>("idempotencia");
+    idempotencia
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! { "key": 1 })
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+            None,
+        )
+        .await?;
+    let ttl = idempotencia
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! { "created_at": 1 })
+                .options(
+                    IndexOptions::builder()
+                        .expire_after(std::time::Duration::from_secs(ventana_horas * 3600))
+                        .build(),
+                )
+                .build(),
+            None,
+        )
+        .await;
+    match ttl {
+        Ok(_) => {}
+        // Ya existía con otra ventana (cambiaron IDEMPOTENCIA_VENTANA_HORAS): se ajusta en su lugar
+        Err(e) if es_conflicto_de_opciones(&e) => {
+            db.run_command(
+                doc! {
+                    "collMod": "idempotencia",
+                    "index": { "keyPattern": { "created_at": 1 }, "expireAfterSeconds": (ventana_horas * 3600) as i64 },
+                },
+                None,
+            )
+            .await?;
+            println!("🔧 Ventana de idempotencia ajustada a {} horas", ventana_horas);
+        }
+        Err(e) => return Err(e),
+    }
+
+    // Un código, un imán: la generación por bloques (y sus reintentos) y la importación
+    // dependen de esto. Si la base ya trae repetidos no se puede crear: avisamos y seguimos.
+    let codigo_unico = IndexModel::builder()
+        .keys(doc! { "codigo": 1 })
+        .options(IndexOptions::builder().unique(true).build())
+        .build();
+    if let Err(e) = db.collection::<mongodb::bson::Document>("imanes").create_index(codigo_unico, None).await {
+        eprintln!("⚠️ No se pudo crear el índice único de códigos (¿hay códigos repetidos?): {}", e);
+    }
+
+    // Las exportaciones (y sus re-descargas) buscan sus imanes por export_id
+    db.collection::<mongodb::bson::Document>("imanes")
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! { "export_id": 1 })
+                .options(IndexOptions::builder().sparse(true).build())
+                .build(),
+            None,
+        )
+        .await?;
+
+    // Una configuración por lote (nombre + fecha). El índice viejo era solo por nombre:
+    // si no existe, drop_index falla y no pasa nada
+    let lotes_config = db.collection::<mongodb::bson::Document>("lotes_config");
+    let _ = lotes_config.drop_index("lote_nombre_1", None).await;
+    lotes_config
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! { "lote_nombre": 1, "lote_fecha": 1 })
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+            None,
+        )
+        .await?;
+
+    // Un reporte pendiente por persona por imán (ya resuelto, puede volver a reportarlo);
+    // la cola de moderación los busca por estado y el límite por IP por red
+    let reportes = db.collection::<mongodb::bson::Document>("reportes");
+    let un_pendiente = IndexModel::builder()
+        .keys(doc! { "codigo": 1, "huella": 1 })
+        .options(
+            IndexOptions::builder()
+                .unique(true)
+                .partial_filter_expression(doc! { "estado": "pendiente" })
+                .build(),
+        )
+        .build();
+    match reportes.create_index(un_pendiente.clone(), None).await {
+        Ok(_) => {}
+        // El índice de antes era único para siempre: se cambia por el parcial
+        Err(e) if es_conflicto_de_opciones(&e) => {
+            reportes.drop_index("codigo_1_huella_1", None).await?;
+            reportes.create_index(un_pendiente, None).await?;
+            println!("🔧 Índice de reportes: ahora solo un pendiente por persona");
+        }
+        Err(e) => return Err(e),
+    }
+    reportes
+        .create_index(IndexModel::builder().keys(doc! { "estado": 1, "at": -1 }).build(), None)
+        .await?;
+    reportes
+        .create_index(IndexModel::builder().keys(doc! { "red": 1, "at": -1 }).build(), None)
+        .await?;
+
+    Ok(())
+}
+
+// El índice ya existe con otras opciones (IndexOptionsConflict 85, IndexKeySpecsConflict 86)
+fn es_conflicto_de_opciones(e: &Error) -> bool {
+    matches!(e.kind.as_ref(), ErrorKind::Command(ce) if ce.code == 85 || ce.code == 86)
+}
+
+/// En un insert_many ordenado: posición del primer documento que violó un índice único
+/// (los anteriores sí se insertaron)
+pub fn primer_duplicado(e: &Error) -> Option<usize> {
+    match e.kind.as_ref() {
+        ErrorKind::BulkWrite(falla) => falla.write_errors.as_ref()?.iter().find(|we| we.code == 11000).map(|we| we.index),
+        _ => None,
+    }
+}
+
+/// ¿El error es por violar un índice único? (código 11000 de Mongo)
+pub fn es_clave_duplicada(e: &Error) -> bool {
+    matches!(
+        e.kind.as_ref(),
+        ErrorKind::Write(WriteFailure::WriteError(we)) if we.code == 11000
+    )
+}
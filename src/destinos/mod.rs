@@ -0,0 +1,370 @@
+// src/destinos/mod.rs
+// A dónde manda un imán en el momento del escaneo.
+use axum::http::{header, HeaderMap};
+use mongodb::bson::{oid::ObjectId, DateTime};
+
+use crate::models::{DestinoProgramado, Iman, ReglaDestino, SistemaOperativo, Variante};
+
+// Esquemas que nunca aceptamos como deep link (ejecutan código o abren archivos)
+const ESQUEMAS_PROHIBIDOS: [&str; 7] = ["javascript", "data", "vbscript", "file", "blob", "http", "https"];
+
+// Redes sociales a las que puede mandar cualquier imán
+const DOMINIOS_PERMITIDOS: [&str; 6] = ["instagram.com", "tiktok.com", "facebook.com", "youtube.com", "twitter.com", "x.com"];
+
+// Tiendas de apps: solo para reglas por sistema operativo (App Store en iPhone, Play Store en Android)
+const TIENDAS_APPS: [&str; 3] = ["apps.apple.com", "itunes.apple.com", "play.google.com"];
+
+// HTTPS y URL bien formada; regresa el dominio en minúsculas
+fn host_https(url: &str) -> Result<String, &'static str> {
+    match url::Url::parse(url) {
+        Ok(u) if u.scheme() == "https" && url.starts_with("https://") => match u.host_str() {
+            Some(host) => Ok(host.trim_end_matches('.').to_ascii_lowercase()),
+            None => Err("❌ Por seguridad, solo aceptamos enlaces seguros (https://)"),
+        },
+        _ => Err("❌ Por seguridad, solo aceptamos enlaces seguros (https://)"),
+    }
+}
+
+// ¿El dominio es uno de la lista o un subdominio suyo? (www.instagram.com sí, instagram.com.evil.net no)
+fn dominio_en(host: &str, lista: &[&str]) -> bool {
+    lista.iter().any(|d| host == *d || host.ends_with(&format!(".{}", d)))
+}
+
+/// Reglas que aplican a cualquier destino que ponga un dueño (setup o programación).
+/// Regresa el mensaje de error listo para mostrar.
+pub fn validar_destino(url: &str) -> Result<(), &'static str> {
+    let host = host_https(url)?;
+
+    // Que sea una URL real de redes sociales (por el dominio, no por el texto:
+    // "https://evil.com/?instagram.com" no pasa). Evita "https://mi-sitio-de-virus.com"
+    if !dominio_en(&host, &DOMINIOS_PERMITIDOS) {
+        return Err("⚠️ Por ahora solo permitimos redes sociales reconocidas (TikTok, Instagram, Youtube, etc).");
+    }
+
+    Ok(())
+}
+
+/// Destino de una regla: lo mismo que validar_destino, más las tiendas de apps
+pub fn validar_destino_regla(url: &str) -> Result<(), &'static str> {
+    let host = host_https(url)?;
+    if !dominio_en(&host, &DOMINIOS_PERMITIDOS) && !dominio_en(&host, &TIENDAS_APPS) {
+        return Err("⚠️ Una regla solo puede mandar a redes sociales reconocidas o a la App Store / Play Store.");
+    }
+    Ok(())
+}
+
+/// La programación vigente ahora mismo. Si se enciman, gana la que empezó más
+/// recientemente (para que una promo corta pise a una larga).
+pub fn programado_vigente(iman: &Iman, ahora: DateTime) -> Option<&DestinoProgramado> {
+    iman.programacion
+        .iter()
+        .filter(|p| p.inicio.is_none_or(|i| i <= ahora) && p.fin.is_none_or(|f| ahora < f))
+        .max_by_key(|p| p.inicio.map(|i| i.timestamp_millis()).unwrap_or(i64::MIN))
+}
+
+/// El destino que toca ahora: la programación vigente o, si no hay, el target_url de siempre
+pub fn url_vigente(iman: &Iman, ahora: DateTime) -> Option<&str> {
+    programado_vigente(iman, ahora)
+        .map(|p| p.url.as_str())
+        .or(iman.target_url.as_deref())
+        .filter(|u| !u.is_empty())
+}
+
+/// Lo que sabemos del teléfono que escaneó (sale de los headers del request)
+pub struct Cliente {
+    pub so: Option<SistemaOperativo>,
+    // Idioma principal, solo la parte primaria ("es-MX" -> "es")
+    pub idioma: Option<String>,
+}
+
+impl Cliente {
+    pub fn desde_headers(headers: &HeaderMap) -> Self {
+        let user_agent = headers.get(header::USER_AGENT).and_then(|v| v.to_str().ok()).unwrap_or("");
+        // Android va primero: algunos navegadores de Android también dicen "like iPhone"
+        let so = if user_agent.contains("Android") {
+            Some(SistemaOperativo::Android)
+        } else if ["iPhone", "iPad", "iPod"].iter().any(|d| user_agent.contains(d)) {
+            Some(SistemaOperativo::Ios)
+        } else {
+            None
+        };
+
+        let idioma = headers
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(idioma_principal);
+
+        Cliente { so, idioma }
+    }
+}
+
+/// "es-MX,es;q=0.9,en;q=0.8" -> "es". El de mayor q (empates: el primero que aparece).
+fn idioma_principal(accept_language: &str) -> Option<String> {
+    accept_language
+        .split(',')
+        .filter_map(|parte| {
+            let mut trozos = parte.trim().split(';');
+            let etiqueta = trozos.next()?.trim();
+            let q = trozos
+                .find_map(|t| t.trim().strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            let primario = etiqueta.split('-').next()?.to_ascii_lowercase();
+            (primario.len() == 2 && q > 0.0).then_some((primario, q))
+        })
+        .fold(None, |mejor: Option<(String, f32)>, (idioma, q)| match mejor {
+            Some((_, q_mejor)) if q_mejor >= q => mejor,
+            _ => Some((idioma, q)),
+        })
+        .map(|(idioma, _)| idioma)
+}
+
+/// La primera regla (en el orden en que se agregaron) que le queda a este cliente
+pub fn regla_aplicable<'a>(iman: &'a Iman, cliente: &Cliente) -> Option<&'a ReglaDestino> {
+    iman.reglas.iter().find(|r| {
+        r.so.is_none_or(|so| cliente.so == Some(so))
+            && r.idioma.as_deref().is_none_or(|i| cliente.idioma.as_deref() == Some(i))
+    })
+}
+
+/// El destino que le tocó a un escaneo
+pub struct Destino<'a> {
+    pub url: &'a str,
+    // Si la regla trae deep link, se intenta antes de `url`
+    pub deep_link: Option<&'a str>,
+    // Si salió de la prueba A/B, cuál variante fue (para contarla y fijarla en la cookie)
+    pub variante: Option<&'a Variante>,
+}
+
+/// A dónde mandar a este cliente ahora: una programación vigente gana sobre todo
+/// (es temporal a propósito); luego la regla que le quede; luego la prueba A/B
+/// y, si no hay, el target_url. `asignada` es la variante que ya le tocó antes (cookie).
+pub fn destino_para<'a>(
+    iman: &'a Iman,
+    cliente: &Cliente,
+    asignada: Option<&str>,
+    ahora: DateTime,
+) -> Option<Destino<'a>> {
+    if let Some(programado) = programado_vigente(iman, ahora) {
+        return Some(Destino { url: &programado.url, deep_link: None, variante: None });
+    }
+    if let Some(regla) = regla_aplicable(iman, cliente) {
+        return Some(Destino { url: &regla.url, deep_link: regla.deep_link.as_deref(), variante: None });
+    }
+    if let Some(variante) = elegir_variante(iman, asignada) {
+        return Some(Destino { url: &variante.url, deep_link: None, variante: Some(variante) });
+    }
+    iman.target_url
+        .as_deref()
+        .filter(|u| !u.is_empty())
+        .map(|url| Destino { url, deep_link: None, variante: None })
+}
+
+/// La variante que ya tenía el visitante (si sigue existiendo) o una nueva al azar según los pesos
+pub fn elegir_variante<'a>(iman: &'a Iman, asignada: Option<&str>) -> Option<&'a Variante> {
+    if let Some(id) = asignada
+        && let Some(variante) = iman.variantes.iter().find(|v| v.id.to_hex() == id && v.peso > 0)
+    {
+        return Some(variante);
+    }
+
+    let total: u32 = iman.variantes.iter().map(|v| v.peso).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut tiro = rand::random_range(0..total);
+    iman.variantes.iter().find(|v| {
+        if tiro < v.peso {
+            return true;
+        }
+        tiro -= v.peso;
+        false
+    })
+}
+
+/// Arma una variante A/B desde el formulario del admin
+pub fn nueva_variante(nombre: &str, url: &str, peso: u32) -> Result<Variante, &'static str> {
+    let nombre = nombre.trim();
+    if nombre.is_empty() || nombre.chars().count() > 30 {
+        return Err("⚠️ Ponle un nombre corto a la variante (A, B, \"Landing nueva\"...).");
+    }
+    // Mismas reglas que cualquier otro destino
+    let url = url.trim();
+    validar_destino(url)?;
+    if !(1..=100).contains(&peso) {
+        return Err("⚠️ El peso va de 1 a 100.");
+    }
+
+    Ok(Variante { id: ObjectId::new(), nombre: nombre.to_string(), url: url.to_string(), peso, visitas: 0 })
+}
+
+/// Arma una regla desde el formulario del admin
+pub fn nueva_regla(so: &str, idioma: &str, url: &str, deep_link: &str) -> Result<ReglaDestino, &'static str> {
+    let so = match so {
+        "" => None,
+        "ios" => Some(SistemaOperativo::Ios),
+        "android" => Some(SistemaOperativo::Android),
+        _ => return Err("⚠️ Sistema operativo no válido."),
+    };
+
+    let idioma = Some(idioma.trim().to_ascii_lowercase()).filter(|i| !i.is_empty());
+    if let Some(i) = &idioma
+        && (i.len() != 2 || !i.chars().all(|c| c.is_ascii_lowercase()))
+    {
+        return Err("⚠️ El idioma va en código de dos letras (es, en, fr...).");
+    }
+
+    if so.is_none() && idioma.is_none() {
+        return Err("⚠️ Una regla necesita al menos un sistema operativo o un idioma.");
+    }
+
+    // Mismas reglas que cualquier otro destino, más las tiendas de apps
+    let url = url.trim();
+    validar_destino_regla(url)?;
+
+    let deep_link = Some(deep_link.trim().to_string()).filter(|d| !d.is_empty());
+    if let Some(d) = &deep_link {
+        match url::Url::parse(d) {
+            Ok(u) if !ESQUEMAS_PROHIBIDOS.contains(&u.scheme()) => {}
+            _ => return Err("⚠️ El deep link debe ser del tipo app:// (ej. instagram://user?username=...)."),
+        }
+    }
+
+    Ok(ReglaDestino { id: ObjectId::new(), so, idioma, url: url.to_string(), deep_link })
+}
+
+// Lo único que se puede usar dentro de una plantilla UTM
+const VARIABLES_UTM: [&str; 2] = ["{lote}", "{codigo}"];
+
+/// Revisa y normaliza una plantilla UTM ("?utm_source=iman&utm_campaign={lote}").
+/// Regresa None si viene vacía (= quitar la plantilla).
+pub fn validar_utm(plantilla: &str) -> Result<Option<String>, &'static str> {
+    let plantilla = plantilla.trim().trim_start_matches('?');
+    if plantilla.is_empty() {
+        return Ok(None);
+    }
+    if plantilla.len() > 500 {
+        return Err("⚠️ La plantilla UTM es demasiado larga.");
+    }
+
+    for (clave, valor) in url::form_urlencoded::parse(plantilla.as_bytes()) {
+        if clave.trim().is_empty() {
+            return Err("⚠️ Cada parámetro necesita nombre (clave=valor&clave=valor).");
+        }
+        // Quitamos las variables conocidas; si queda alguna llave, es una variable que no existe
+        let sin_variables = VARIABLES_UTM.iter().fold(valor.to_string(), |v, var| v.replace(var, ""));
+        if sin_variables.contains('{') || sin_variables.contains('}') {
+            return Err("⚠️ Solo se pueden usar las variables {lote} y {codigo}.");
+        }
+    }
+
+    Ok(Some(plantilla.to_string()))
+}
+
+/// Agrega los parámetros de la plantilla al destino, respetando su query string y su #fragmento.
+/// Si el destino ya trae un parámetro con el mismo nombre, se queda el del cliente.
+pub fn con_utm(destino: &str, plantilla: &str, iman: &Iman) -> String {
+    let Ok(mut url) = url::Url::parse(destino) else {
+        return destino.to_string();
+    };
+
+    let existentes: Vec<String> = url.query_pairs().map(|(k, _)| k.into_owned()).collect();
+    let lote = iman.lote_nombre.as_deref().unwrap_or("");
+    let nuevos: Vec<(String, String)> = url::form_urlencoded::parse(plantilla.as_bytes())
+        .filter(|(k, _)| !existentes.iter().any(|e| e == k))
+        .map(|(k, v)| (k.into_owned(), v.replace("{lote}", lote).replace("{codigo}", &iman.codigo)))
+        .collect();
+
+    if !nuevos.is_empty() {
+        url.query_pairs_mut().extend_pairs(nuevos);
+    }
+    url.to_string()
+}
+
+/// "2025-12-01T00:00" (input datetime-local, hora del navegador) + desfase en minutos
+/// de `Date.getTimezoneOffset()` -> DateTime en UTC
+pub fn parse_fecha_local(valor: &str, tz_offset_min: i64) -> Option<DateTime> {
+    let valor = valor.trim();
+    if valor.is_empty() {
+        return None;
+    }
+    let local = chrono::NaiveDateTime::parse_from_str(valor, "%Y-%m-%dT%H:%M").ok()?;
+    let utc = local + chrono::Duration::minutes(tz_offset_min);
+    Some(DateTime::from_millis(utc.and_utc().timestamp_millis()))
+}
+
+/// Arma un destino programado a partir del formulario (admin o dueño)
+pub fn nuevo_programado(
+    url: &str,
+    inicio: &str,
+    fin: &str,
+    tz_offset_min: i64,
+) -> Result<DestinoProgramado, &'static str> {
+    let inicio = parse_fecha_local(inicio, tz_offset_min);
+    let fin = parse_fecha_local(fin, tz_offset_min);
+
+    if inicio.is_none() && fin.is_none() {
+        return Err("⚠️ Pon al menos una fecha de inicio o de fin.");
+    }
+    if let (Some(i), Some(f)) = (inicio, fin)
+        && f <= i
+    {
+        return Err("⚠️ La fecha de fin debe ser después del inicio.");
+    }
+
+    Ok(DestinoProgramado { id: ObjectId::new(), url: url.trim().to_string(), inicio, fin })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn iman() -> Iman {
+        Iman { codigo: "ABC123".to_string(), lote_nombre: Some("feria".to_string()), ..Default::default() }
+    }
+
+    #[test]
+    fn con_utm_agrega_y_sustituye_variables() {
+        let url = con_utm("https://instagram.com/tienda", "utm_source=iman&utm_campaign={lote}&ref={codigo}", &iman());
+        assert_eq!(url, "https://instagram.com/tienda?utm_source=iman&utm_campaign=feria&ref=ABC123");
+    }
+
+    #[test]
+    fn con_utm_respeta_los_parametros_del_cliente() {
+        let url = con_utm("https://instagram.com/tienda?utm_source=cliente", "utm_source=iman&utm_medium=nfc", &iman());
+        assert_eq!(url, "https://instagram.com/tienda?utm_source=cliente&utm_medium=nfc");
+    }
+
+    #[test]
+    fn con_utm_conserva_el_fragmento() {
+        let url = con_utm("https://instagram.com/tienda#fotos", "utm_source=iman", &iman());
+        assert_eq!(url, "https://instagram.com/tienda?utm_source=iman#fotos");
+    }
+
+    #[test]
+    fn con_utm_deja_igual_una_url_invalida() {
+        assert_eq!(con_utm("no es url", "utm_source=iman", &iman()), "no es url");
+    }
+
+    #[test]
+    fn destino_por_dominio_no_por_texto() {
+        assert!(validar_destino("https://instagram.com/tienda").is_ok());
+        assert!(validar_destino("https://www.instagram.com/tienda").is_ok());
+        assert!(validar_destino("https://evil.com/?instagram.com").is_err());
+        assert!(validar_destino("https://instagram.com.evil.net/").is_err());
+        assert!(validar_destino("https://notinstagram.com/").is_err());
+        assert!(validar_destino("http://instagram.com/tienda").is_err());
+    }
+
+    #[test]
+    fn regla_acepta_tiendas_de_apps() {
+        let ios = nueva_regla("ios", "", "https://apps.apple.com/mx/app/mi-app/id123456789", "").unwrap();
+        assert_eq!(ios.so, Some(SistemaOperativo::Ios));
+        let android = nueva_regla("android", "", "https://play.google.com/store/apps/details?id=com.mi.app", "").unwrap();
+        assert_eq!(android.so, Some(SistemaOperativo::Android));
+
+        assert!(nueva_regla("ios", "", "https://apps.apple.com.evil.net/app", "").is_err());
+        // Las tiendas son solo para reglas
+        assert!(validar_destino("https://play.google.com/store/apps/details?id=com.mi.app").is_err());
+    }
+}
@@ -0,0 +1,162 @@
+// src/estados/mod.rs
+// Máquina de estados de los imanes. Todas las transiciones pasan por aquí:
+// el filtro de Mongo solo deja cambiar documentos que estén en un estado de origen válido.
+use mongodb::bson::{doc, DateTime, Document};
+use mongodb::options::{FindOneAndUpdateOptions, ReturnDocument};
+
+use crate::db::DB;
+use crate::models::{EstadoIman, Iman};
+
+use EstadoIman::*;
+
+/// Desde qué estados se puede llegar a `destino`
+pub fn origenes(destino: EstadoIman) -> &'static [EstadoIman] {
+    match destino {
+        // Solo al desmarcar una exportación
+        Generated => &[Exported],
+        Exported => &[Generated],
+        Encoded => &[Generated, Exported],
+        Sold => &[Generated, Exported, Encoded],
+        // Cualquiera que no tenga dueño lo puede reclamar; pausado/deshabilitado se reactivan
+        Claimed => &[Generated, Exported, Encoded, Sold, Paused, Disabled],
+        Paused => &[Claimed],
+        Disabled => &[Generated, Exported, Encoded, Sold, Claimed, Paused, Lost],
+        Lost => &[Generated, Exported, Encoded, Sold, Claimed, Paused, Disabled],
+    }
+}
+
+// Los que todavía no tienen dueño (los únicos que se pueden reclamar desde la página de setup)
+pub const SIN_DUENO: [EstadoIman; 4] = [Generated, Exported, Encoded, Sold];
+
+/// Filtro `{ estado: { $in: [...] } }`
+pub fn filtro_estados(estados: &[EstadoIman]) -> Document {
+    let estados: Vec<&str> = estados.iter().map(|e| clave(*e)).collect();
+    doc! { "estado": { "$in": estados } }
+}
+
+/// Filtro de los documentos que pueden pasar a `destino`, para combinar con el de cada caso
+pub fn filtro_origen(destino: EstadoIman) -> Document {
+    filtro_estados(origenes(destino))
+}
+
+/// Update que aplica la transición (estado, espejo `active` e historial).
+/// `extra` se mezcla en el `$set` (target_url, export_id, ...).
+pub fn update(destino: EstadoIman, motivo: Option<&str>, extra: Document) -> Document {
+    let ahora = DateTime::now();
+    let mut set = doc! { "estado": clave(destino), "active": destino == Claimed };
+    set.extend(extra);
+
+    let mut cambio = doc! { "estado": clave(destino), "at": ahora };
+    if let Some(m) = motivo {
+        cambio.insert("motivo", m);
+    }
+
+    doc! { "$set": set, "$push": { "historial": cambio } }
+}
+
+/// Como se guarda en Mongo (igual que el serde de EstadoIman)
+pub fn clave(estado: EstadoIman) -> &'static str {
+    match estado {
+        Generated => "generated",
+        Exported => "exported",
+        Encoded => "encoded",
+        Sold => "sold",
+        Claimed => "claimed",
+        Paused => "paused",
+        Disabled => "disabled",
+        Lost => "lost",
+    }
+}
+
+// Por qué no se pudo hacer una transición
+#[derive(Debug)]
+pub enum ErrorTransicion {
+    NoExiste,
+    NoPermitida { desde: EstadoIman, hacia: EstadoIman },
+    Db(mongodb::error::Error),
+}
+
+impl std::fmt::Display for ErrorTransicion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorTransicion::NoExiste => write!(f, "El imán no existe"),
+            ErrorTransicion::NoPermitida { desde, hacia } => {
+                write!(f, "No se puede pasar de \"{}\" a \"{}\"", etiqueta(*desde), etiqueta(*hacia))
+            }
+            ErrorTransicion::Db(e) => write!(f, "DB error: {}", e),
+        }
+    }
+}
+
+/// Cambia el estado de UN imán de forma atómica y regresa cómo quedó.
+/// `solo_desde` restringe todavía más los orígenes (ej. el setup público solo reclama SIN_DUENO).
+pub async fn transicionar(
+    db: &DB,
+    codigo: &str,
+    destino: EstadoIman,
+    solo_desde: Option<&[EstadoIman]>,
+    motivo: Option<&str>,
+    extra: Document,
+) -> Result<Iman, ErrorTransicion> {
+    let collection = db.collection::<Iman>("imanes");
+
+    let permitidos: Vec<EstadoIman> = origenes(destino)
+        .iter()
+        .copied()
+        .filter(|e| solo_desde.is_none_or(|s| s.contains(e)))
+        .collect();
+    let mut filter = filtro_estados(&permitidos);
+    filter.insert("codigo", codigo);
+    let options = FindOneAndUpdateOptions::builder()
+        .return_document(ReturnDocument::After)
+        .build();
+
+    match collection.find_one_and_update(filter, update(destino, motivo, extra), options).await {
+        Ok(Some(iman)) => Ok(iman),
+        Ok(None) => {
+            // No se actualizó: o no existe o su estado actual no lo permite
+            match collection.find_one(doc! { "codigo": codigo }, None).await {
+                Ok(Some(iman)) => Err(ErrorTransicion::NoPermitida { desde: iman.estado, hacia: destino }),
+                Ok(None) => Err(ErrorTransicion::NoExiste),
+                Err(e) => Err(ErrorTransicion::Db(e)),
+            }
+        }
+        Err(e) => Err(ErrorTransicion::Db(e)),
+    }
+}
+
+/// Documentos de antes de la máquina de estados: les asignamos el estado
+/// que corresponde a sus banderas viejas. Solo toca los que no tienen `estado`.
+pub async fn migrar(db: &DB) {
+    let collection = db.collection::<Document>("imanes");
+    let casos = [
+        (doc! { "active": true }, Claimed),
+        (doc! { "target_url": { "$nin": [null, ""] } }, Disabled),
+        (doc! { "exported": true }, Exported),
+        (doc! {}, Generated),
+    ];
+
+    for (condicion, estado) in casos {
+        let mut filter = doc! { "estado": { "$exists": false } };
+        filter.extend(condicion);
+        match collection.update_many(filter, update(estado, Some("migración"), doc! {}), None).await {
+            Ok(r) if r.modified_count > 0 => println!("🔁 {} imanes migrados a \"{}\"", r.modified_count, etiqueta(estado)),
+            Ok(_) => {}
+            Err(e) => eprintln!("❌ Error migrando estados: {}", e),
+        }
+    }
+}
+
+/// Así se ve EstadoIman en las plantillas
+pub fn etiqueta(estado: EstadoIman) -> &'static str {
+    match estado {
+        Generated => "Generado",
+        Exported => "Exportado",
+        Encoded => "Grabado",
+        Sold => "Vendido",
+        Claimed => "Activo",
+        Paused => "Pausado",
+        Disabled => "Deshabilitado",
+        Lost => "Perdido",
+    }
+}
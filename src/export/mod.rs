@@ -0,0 +1,367 @@
+// src/export/mod.rs
+// Todo lo que tiene que ver con armar archivos de exportación (CSV, Excel, JSON).
+use axum::body::Bytes;
+use futures::{future, stream, Stream, StreamExt, TryStreamExt};
+use mongodb::bson::DateTime;
+use rust_xlsxwriter::{Color, ExcelDateTime, Format, FormatBorder, Workbook, XlsxError};
+
+use crate::{codes, estados, nfc};
+use crate::models::Iman;
+
+// Columnas que se pueden pedir con ?cols=codigo,url,lote,...
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Columna {
+    Codigo,
+    Url,
+    Lote,
+    Active,
+    Visitas,
+    ActivatedAt,
+    LastScanAt,
+    TargetUrl,
+    Secuencia,
+    // Mensaje NDEF del link en hex (para codificadoras masivas)
+    NdefHex,
+    Estado,
+}
+
+impl Columna {
+    pub const TODAS: [Columna; 11] = [
+        Columna::Secuencia,
+        Columna::Codigo,
+        Columna::Url,
+        Columna::Lote,
+        Columna::Estado,
+        Columna::Active,
+        Columna::Visitas,
+        Columna::ActivatedAt,
+        Columna::LastScanAt,
+        Columna::TargetUrl,
+        Columna::NdefHex,
+    ];
+
+    pub fn parse(s: &str) -> Option<Columna> {
+        match s.trim() {
+            "codigo" => Some(Columna::Codigo),
+            "url" | "url_completa" => Some(Columna::Url),
+            "lote" => Some(Columna::Lote),
+            "active" => Some(Columna::Active),
+            "visitas" => Some(Columna::Visitas),
+            "activated_at" => Some(Columna::ActivatedAt),
+            "last_scan_at" => Some(Columna::LastScanAt),
+            "target_url" => Some(Columna::TargetUrl),
+            "secuencia" => Some(Columna::Secuencia),
+            "ndef_hex" => Some(Columna::NdefHex),
+            "estado" => Some(Columna::Estado),
+            _ => None,
+        }
+    }
+
+    pub fn encabezado(self) -> &'static str {
+        match self {
+            Columna::Codigo => "codigo",
+            Columna::Url => "url_completa",
+            Columna::Lote => "lote",
+            Columna::Active => "active",
+            Columna::Visitas => "visitas",
+            Columna::ActivatedAt => "activated_at",
+            Columna::LastScanAt => "last_scan_at",
+            Columna::TargetUrl => "target_url",
+            Columna::Secuencia => "secuencia",
+            Columna::NdefHex => "ndef_hex",
+            Columna::Estado => "estado",
+        }
+    }
+
+    /// El valor con su tipo (para Excel y JSON, que distinguen números, fechas y vacíos)
+    pub fn valor_tipado(self, iman: &Iman, base_url: &str) -> Valor {
+        match self {
+            Columna::Codigo => Valor::Texto(iman.codigo.clone()),
+            Columna::Url => Valor::Texto(format!("{}/v/{}", base_url, iman.codigo)),
+            Columna::Lote => iman.lote_nombre.clone().map_or(Valor::Nulo, Valor::Texto),
+            Columna::Active => Valor::Bool(iman.active),
+            Columna::Visitas => Valor::Entero(iman.visitas as i64),
+            Columna::ActivatedAt => iman.activated_at.map_or(Valor::Nulo, Valor::Fecha),
+            Columna::LastScanAt => iman.last_scan_at.map_or(Valor::Nulo, Valor::Fecha),
+            Columna::TargetUrl => iman.target_url.clone().map_or(Valor::Nulo, Valor::Texto),
+            Columna::Secuencia => codes::secuencia(&iman.codigo).map_or(Valor::Nulo, |n| Valor::Entero(n as i64)),
+            Columna::Estado => Valor::Texto(estados::clave(iman.estado).to_string()),
+            Columna::NdefHex => Valor::Texto(nfc::hex(&nfc::mensaje_uri(&format!("{}/v/{}", base_url, iman.codigo)))),
+        }
+    }
+
+    pub fn valor(self, iman: &Iman, base_url: &str) -> String {
+        self.valor_tipado(iman, base_url).texto()
+    }
+}
+
+// Un valor de celda
+pub enum Valor {
+    Texto(String),
+    Entero(i64),
+    Bool(bool),
+    Fecha(DateTime),
+    Nulo,
+}
+
+impl Valor {
+    // Como va en el CSV: todo texto, fechas en ISO 8601 y vacío si no hay
+    fn texto(self) -> String {
+        match self {
+            Valor::Texto(t) => t,
+            Valor::Entero(n) => n.to_string(),
+            Valor::Bool(b) => b.to_string(),
+            Valor::Fecha(dt) => fecha_iso(Some(dt)),
+            Valor::Nulo => String::new(),
+        }
+    }
+
+    fn json(self) -> serde_json::Value {
+        match self {
+            Valor::Texto(t) => t.into(),
+            Valor::Entero(n) => n.into(),
+            Valor::Bool(b) => b.into(),
+            Valor::Fecha(dt) => fecha_iso(Some(dt)).into(),
+            Valor::Nulo => serde_json::Value::Null,
+        }
+    }
+}
+
+fn fecha_iso(fecha: Option<DateTime>) -> String {
+    fecha
+        .and_then(|dt| chrono::DateTime::from_timestamp_millis(dt.timestamp_millis()))
+        .map(|dt| dt.to_rfc3339())
+        .unwrap_or_default()
+}
+
+/// "?cols=codigo,url" -> columnas. Sin parámetro (o si nada es válido) usamos
+/// las dos de siempre para no romper a quien ya consume el CSV.
+pub fn parse_columnas(cols: Option<&str>) -> Vec<Columna> {
+    let columnas: Vec<Columna> = match cols {
+        Some("all") => Columna::TODAS.to_vec(),
+        Some(lista) => lista.split(',').filter_map(Columna::parse).collect(),
+        None => Vec::new(),
+    };
+
+    if columnas.is_empty() {
+        vec![Columna::Codigo, Columna::Url]
+    } else {
+        columnas
+    }
+}
+
+// Marca de orden de bytes: Excel la necesita para leer acentos en UTF-8
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+// Escribe un solo registro con escapado correcto (comas, comillas, saltos de línea)
+fn registro_csv<I, T>(campos: I) -> std::io::Result<Vec<u8>>
+where
+    I: IntoIterator<Item = T>,
+    T: AsRef<[u8]>,
+{
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(campos)?;
+    writer.into_inner().map_err(|e| e.into_error())
+}
+
+/// Fila de encabezados (con BOM opcional para Excel)
+pub fn encabezado_csv(columnas: &[Columna], bom: bool) -> std::io::Result<Vec<u8>> {
+    let mut salida = Vec::new();
+    if bom {
+        salida.extend_from_slice(BOM);
+    }
+    salida.extend(registro_csv(columnas.iter().map(|c| c.encabezado()))?);
+    Ok(salida)
+}
+
+pub fn fila_csv(iman: &Iman, columnas: &[Columna], base_url: &str) -> std::io::Result<Vec<u8>> {
+    registro_csv(columnas.iter().map(|c| c.valor(iman, base_url)))
+}
+
+// Filas por pedazo del stream: suficiente para no mandar un chunk HTTP por imán
+const FILAS_POR_CHUNK: usize = 500;
+
+/// Convierte un stream de imanes (normalmente el cursor de Mongo) en el cuerpo
+/// del CSV, pedazo por pedazo. La memoria no crece con el tamaño del lote.
+pub fn stream_csv<S>(
+    imanes: S,
+    columnas: Vec<Columna>,
+    base_url: String,
+    bom: bool,
+) -> impl Stream<Item = std::io::Result<Bytes>> + Send
+where
+    S: Stream<Item = Result<Iman, mongodb::error::Error>> + Send + 'static,
+{
+    let encabezado = stream::once(future::ready(encabezado_csv(&columnas, bom).map(Bytes::from)));
+
+    let filas = imanes
+        .map_err(std::io::Error::other)
+        .try_chunks(FILAS_POR_CHUNK)
+        .map_err(|e| e.1)
+        .and_then(move |chunk| {
+            let mut pedazo = Vec::new();
+            let resultado = chunk
+                .iter()
+                .try_for_each(|iman| fila_csv(iman, &columnas, &base_url).map(|f| pedazo.extend(f)))
+                .map(|_| Bytes::from(pedazo));
+            future::ready(resultado)
+        });
+
+    encabezado.chain(filas)
+}
+
+// Formato del archivo: ?formato=csv|xlsx|json|ndjson
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Formato {
+    Csv,
+    Xlsx,
+    Json,
+    // Un objeto JSON por línea (para procesar sin cargar todo en memoria)
+    Ndjson,
+}
+
+impl Formato {
+    /// Sin parámetro es CSV, como siempre
+    pub fn parse(s: Option<&str>) -> Option<Formato> {
+        match s.map(str::trim).unwrap_or("csv") {
+            "" | "csv" => Some(Formato::Csv),
+            "xlsx" | "excel" => Some(Formato::Xlsx),
+            "json" => Some(Formato::Json),
+            "ndjson" | "jsonl" => Some(Formato::Ndjson),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Formato::Csv => "csv",
+            Formato::Xlsx => "xlsx",
+            Formato::Json => "json",
+            Formato::Ndjson => "ndjson",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Formato::Csv => "text/csv; charset=utf-8",
+            Formato::Xlsx => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+            Formato::Json => "application/json",
+            Formato::Ndjson => "application/x-ndjson",
+        }
+    }
+}
+
+fn objeto_json(iman: &Iman, columnas: &[Columna], base_url: &str) -> std::io::Result<Vec<u8>> {
+    let objeto: serde_json::Map<String, serde_json::Value> = columnas
+        .iter()
+        .map(|c| (c.encabezado().to_string(), c.valor_tipado(iman, base_url).json()))
+        .collect();
+    serde_json::to_vec(&objeto).map_err(std::io::Error::other)
+}
+
+/// Igual que `stream_csv`, pero en JSON: un arreglo (`ndjson = false`) o un objeto por línea
+pub fn stream_json<S>(
+    imanes: S,
+    columnas: Vec<Columna>,
+    base_url: String,
+    ndjson: bool,
+) -> impl Stream<Item = std::io::Result<Bytes>> + Send
+where
+    S: Stream<Item = Result<Iman, mongodb::error::Error>> + Send + 'static,
+{
+    let (abre, cierra): (&'static [u8], &'static [u8]) = if ndjson { (b"", b"") } else { (b"[", b"]\n") };
+    let mut primero = true;
+
+    let filas = imanes
+        .map_err(std::io::Error::other)
+        .try_chunks(FILAS_POR_CHUNK)
+        .map_err(|e| e.1)
+        .and_then(move |chunk| {
+            let mut pedazo = Vec::new();
+            let resultado = chunk
+                .iter()
+                .try_for_each(|iman| {
+                    let objeto = objeto_json(iman, &columnas, &base_url)?;
+                    if !ndjson && !std::mem::take(&mut primero) {
+                        pedazo.push(b',');
+                    }
+                    pedazo.extend(objeto);
+                    if ndjson {
+                        pedazo.push(b'\n');
+                    }
+                    Ok(())
+                })
+                .map(|_| Bytes::from(pedazo));
+            future::ready(resultado)
+        });
+
+    stream::once(future::ready(Ok(Bytes::from_static(abre))))
+        .chain(filas)
+        .chain(stream::once(future::ready(Ok(Bytes::from_static(cierra)))))
+}
+
+/// Libro de Excel con tipos de verdad: números, booleanos y fechas como fecha.
+/// XLSX es un ZIP, así que aquí no hay streaming: se arma completo en memoria.
+pub fn xlsx(imanes: &[Iman], columnas: &[Columna], base_url: &str) -> Result<Vec<u8>, XlsxError> {
+    let mut libro = Workbook::new();
+    let hoja = libro.add_worksheet();
+    hoja.set_name("Imanes")?;
+
+    let estilo_encabezado = Format::new()
+        .set_bold()
+        .set_font_color(Color::White)
+        .set_background_color(Color::RGB(0x6D28D9))
+        .set_border_bottom(FormatBorder::Thin);
+    let estilo_fecha = Format::new().set_num_format("yyyy-mm-dd hh:mm");
+
+    for (col, columna) in columnas.iter().enumerate() {
+        hoja.write_string_with_format(0, col as u16, columna.encabezado(), &estilo_encabezado)?;
+    }
+
+    for (i, iman) in imanes.iter().enumerate() {
+        let fila = i as u32 + 1;
+        for (col, columna) in columnas.iter().enumerate() {
+            let col = col as u16;
+            match columna.valor_tipado(iman, base_url) {
+                Valor::Texto(t) => { hoja.write_string(fila, col, t)?; }
+                Valor::Entero(n) => { hoja.write_number(fila, col, n as f64)?; }
+                Valor::Bool(b) => { hoja.write_boolean(fila, col, b)?; }
+                Valor::Fecha(dt) => {
+                    // Excel no sabe de zonas horarias: va en UTC
+                    let fecha = ExcelDateTime::from_timestamp(dt.timestamp_millis() / 1000)?;
+                    hoja.write_datetime_with_format(fila, col, &fecha, &estilo_fecha)?;
+                }
+                Valor::Nulo => {}
+            }
+        }
+    }
+
+    // Encabezado fijo y con filtros, columnas a la medida del contenido
+    hoja.set_freeze_panes(1, 0)?;
+    if !columnas.is_empty() {
+        hoja.autofilter(0, 0, imanes.len() as u32, columnas.len() as u16 - 1)?;
+    }
+    hoja.autofit();
+
+    libro.save_to_buffer()
+}
+
+/// Header Content-Disposition seguro: versión ASCII para navegadores viejos y
+/// `filename*` (RFC 5987) con el nombre real en UTF-8.
+pub fn content_disposition(nombre: &str) -> String {
+    let ascii: String = nombre
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' })
+        .collect();
+
+    let mut utf8 = String::new();
+    for b in nombre.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'.' | b'-' | b'_' | b'~') {
+            utf8.push(b as char);
+        } else {
+            utf8.push_str(&format!("%{:02X}", b));
+        }
+    }
+
+    format!("attachment; filename=\"{}\"; filename*=UTF-8''{}", ascii, utf8)
+}
@@ -0,0 +1,282 @@
+// src/hojas/mod.rs
+// Hojas imprimibles en PDF: una cuadrícula de etiquetas con QR y texto.
+// Todas las medidas están en milímetros, origen arriba a la izquierda
+// (la conversión al origen de PDF, abajo a la izquierda, se hace al dibujar).
+use printpdf::{
+    path::PaintMode, BuiltinFont, Color, IndirectFontRef, Line, Mm, PdfDocument, PdfLayerReference, Point, Rect, Rgb,
+};
+
+use crate::qr;
+
+// Geometría de una hoja de etiquetas
+#[derive(Debug, Clone, Copy)]
+pub struct Plantilla {
+    pub clave: &'static str,
+    pub nombre: &'static str,
+    pub ancho_pagina: f32,
+    pub alto_pagina: f32,
+    pub margen_superior: f32,
+    pub margen_izquierdo: f32,
+    pub ancho_celda: f32,
+    pub alto_celda: f32,
+    // Distancia entre el inicio de una celda y la siguiente (incluye el hueco)
+    pub paso_horizontal: f32,
+    pub paso_vertical: f32,
+    pub columnas: u32,
+    pub filas: u32,
+    // Hojas en blanco que se cortan a mano: llevan marcas de corte
+    pub marcas_corte: bool,
+}
+
+/// Cuadrícula A4 para stickers QR cortados a mano (4 x 6, celdas de 45 mm)
+pub const QR_A4: Plantilla = Plantilla {
+    clave: "qr_a4",
+    nombre: "QR A4 (4 x 6, corte manual)",
+    ancho_pagina: 210.0,
+    alto_pagina: 297.0,
+    margen_superior: 10.5,
+    margen_izquierdo: 10.0,
+    ancho_celda: 45.0,
+    alto_celda: 45.0,
+    paso_horizontal: 48.0,
+    paso_vertical: 46.0,
+    columnas: 4,
+    filas: 6,
+    marcas_corte: true,
+};
+
+// Hojas de etiquetas precortadas (medidas del fabricante).
+// Para agregar otra basta con copiar una entrada y ajustar los números.
+pub const PLANTILLAS: [Plantilla; 4] = [
+    QR_A4,
+    // Avery L7160: A4, 3 x 7, 63.5 x 38.1 mm
+    Plantilla {
+        clave: "l7160",
+        nombre: "Avery L7160 (A4, 21 por hoja)",
+        ancho_pagina: 210.0,
+        alto_pagina: 297.0,
+        margen_superior: 15.15,
+        margen_izquierdo: 7.25,
+        ancho_celda: 63.5,
+        alto_celda: 38.1,
+        paso_horizontal: 66.04,
+        paso_vertical: 38.1,
+        columnas: 3,
+        filas: 7,
+        marcas_corte: false,
+    },
+    // Avery L7163: A4, 2 x 7, 99.1 x 38.1 mm
+    Plantilla {
+        clave: "l7163",
+        nombre: "Avery L7163 (A4, 14 por hoja)",
+        ancho_pagina: 210.0,
+        alto_pagina: 297.0,
+        margen_superior: 15.15,
+        margen_izquierdo: 4.65,
+        ancho_celda: 99.1,
+        alto_celda: 38.1,
+        paso_horizontal: 101.6,
+        paso_vertical: 38.1,
+        columnas: 2,
+        filas: 7,
+        marcas_corte: false,
+    },
+    // Avery 5160: Carta, 3 x 10, 2.625 x 1 pulgadas
+    Plantilla {
+        clave: "5160",
+        nombre: "Avery 5160 (Carta, 30 por hoja)",
+        ancho_pagina: 215.9,
+        alto_pagina: 279.4,
+        margen_superior: 12.7,
+        margen_izquierdo: 4.76,
+        ancho_celda: 66.68,
+        alto_celda: 25.4,
+        paso_horizontal: 69.85,
+        paso_vertical: 25.4,
+        columnas: 3,
+        filas: 10,
+        marcas_corte: false,
+    },
+];
+
+/// Busca una plantilla por su clave (?plantilla=l7160)
+pub fn plantilla(clave: &str) -> Option<&'static Plantilla> {
+    PLANTILLAS.iter().find(|p| p.clave == clave)
+}
+
+impl Plantilla {
+    pub fn por_pagina(&self) -> usize {
+        (self.columnas * self.filas) as usize
+    }
+}
+
+// Lo que va impreso en cada celda
+pub struct Etiqueta {
+    // Contenido del QR
+    pub url: String,
+    // Texto debajo (o a un lado) del QR; la primera línea va en negritas
+    pub lineas: Vec<String>,
+}
+
+const LARGO_MARCA: f32 = 3.0;
+const PT_POR_MM: f32 = 2.834_646;
+
+/// Genera el PDF completo (tantas páginas como hagan falta)
+pub fn pdf(titulo: &str, plantilla: &Plantilla, etiquetas: &[Etiqueta]) -> Result<Vec<u8>, String> {
+    let (doc, pagina, capa) = PdfDocument::new(
+        titulo,
+        Mm(plantilla.ancho_pagina),
+        Mm(plantilla.alto_pagina),
+        "Etiquetas",
+    );
+    let negritas = doc.add_builtin_font(BuiltinFont::HelveticaBold).map_err(|e| e.to_string())?;
+    let normal = doc.add_builtin_font(BuiltinFont::Helvetica).map_err(|e| e.to_string())?;
+    let fuentes = (negritas, normal);
+
+    let mut capa_actual = doc.get_page(pagina).get_layer(capa);
+
+    for (i, etiqueta) in etiquetas.iter().enumerate() {
+        let en_pagina = i % plantilla.por_pagina();
+        if i > 0 && en_pagina == 0 {
+            let (p, c) = doc.add_page(Mm(plantilla.ancho_pagina), Mm(plantilla.alto_pagina), "Etiquetas");
+            capa_actual = doc.get_page(p).get_layer(c);
+        }
+
+        let col = en_pagina as u32 % plantilla.columnas;
+        let fila = en_pagina as u32 / plantilla.columnas;
+        let x = plantilla.margen_izquierdo + col as f32 * plantilla.paso_horizontal;
+        let y = plantilla.margen_superior + fila as f32 * plantilla.paso_vertical;
+
+        dibujar_etiqueta(&capa_actual, plantilla, x, y, etiqueta, &fuentes)?;
+        if plantilla.marcas_corte {
+            dibujar_marcas(&capa_actual, plantilla, x, y);
+        }
+    }
+
+    doc.save_to_bytes().map_err(|e| e.to_string())
+}
+
+// Pasa de "mm desde arriba" a "mm desde abajo" (como lo quiere PDF)
+fn y_pdf(plantilla: &Plantilla, y: f32) -> Mm {
+    Mm(plantilla.alto_pagina - y)
+}
+
+fn dibujar_etiqueta(
+    capa: &PdfLayerReference,
+    plantilla: &Plantilla,
+    x: f32,
+    y: f32,
+    etiqueta: &Etiqueta,
+    (negritas, normal): &(IndirectFontRef, IndirectFontRef),
+) -> Result<(), String> {
+    // El padding deja la "zona de silencio" que el lector de QR necesita alrededor
+    let padding = 3.5;
+    let alto_linea = 3.5;
+    let horizontal = plantilla.ancho_celda >= plantilla.alto_celda * 1.4;
+
+    // Etiquetas anchas: QR a la izquierda y texto a la derecha.
+    // Etiquetas cuadradas: QR arriba y texto abajo.
+    let (lado_qr, texto_x, texto_y) = if horizontal {
+        let lado = plantilla.alto_celda - padding * 2.0;
+        (lado, x + padding * 2.0 + lado, y + padding + alto_linea)
+    } else {
+        let espacio_texto = alto_linea * etiqueta.lineas.len() as f32 + padding;
+        let lado = (plantilla.alto_celda - espacio_texto - padding * 2.0).min(plantilla.ancho_celda - padding * 2.0);
+        (lado, x + padding, y + padding * 2.0 + lado + alto_linea)
+    };
+    let qr_x = if horizontal { x + padding } else { x + (plantilla.ancho_celda - lado_qr) / 2.0 };
+
+    dibujar_qr(capa, plantilla, qr_x, y + padding, lado_qr, &etiqueta.url)?;
+
+    // Lo que no quepa a lo ancho se parte en varias líneas; lo que no quepa a lo alto se omite
+    let ancho_texto = if horizontal { x + plantilla.ancho_celda - padding - texto_x } else { plantilla.ancho_celda - padding * 2.0 };
+    let limite_y = y + plantilla.alto_celda - padding;
+
+    capa.set_fill_color(Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
+    let mut renglon = 0;
+    for (n, linea) in etiqueta.lineas.iter().enumerate() {
+        let (fuente, tamano) = if n == 0 { (negritas, 8.0) } else { (normal, 6.5) };
+
+        for pedazo in partir(linea, caracteres_que_caben(ancho_texto, tamano)) {
+            let base = texto_y + renglon as f32 * alto_linea;
+            if base > limite_y {
+                return Ok(());
+            }
+            capa.use_text(pedazo, tamano, Mm(texto_x), y_pdf(plantilla, base), fuente);
+            renglon += 1;
+        }
+    }
+
+    Ok(())
+}
+
+// Helvetica promedia ~0.55 em por carácter; 1 pt = 0.3528 mm
+fn caracteres_que_caben(ancho_mm: f32, tamano_pt: f32) -> usize {
+    ((ancho_mm / (tamano_pt * 0.55 * 0.3528)) as usize).max(1)
+}
+
+fn partir(texto: &str, max: usize) -> Vec<String> {
+    let chars: Vec<char> = texto.chars().collect();
+    chars.chunks(max).map(|c| c.iter().collect()).collect()
+}
+
+fn dibujar_qr(
+    capa: &PdfLayerReference,
+    plantilla: &Plantilla,
+    x: f32,
+    y: f32,
+    lado: f32,
+    contenido: &str,
+) -> Result<(), String> {
+    let (ancho, modulos) = qr::modulos(contenido).map_err(|e| e.to_string())?;
+    let modulo = lado / ancho as f32;
+
+    capa.set_fill_color(Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
+
+    // Una tira por cada racha horizontal de módulos oscuros (mucho menos peso que un cuadro por módulo)
+    for fila in 0..ancho {
+        let mut col = 0;
+        while col < ancho {
+            if !modulos[fila * ancho + col] {
+                col += 1;
+                continue;
+            }
+            let inicio = col;
+            while col < ancho && modulos[fila * ancho + col] {
+                col += 1;
+            }
+
+            let x1 = x + inicio as f32 * modulo;
+            let x2 = x + col as f32 * modulo;
+            let y_arriba = y + fila as f32 * modulo;
+            let y_abajo = y_arriba + modulo;
+            capa.add_rect(
+                Rect::new(Mm(x1), y_pdf(plantilla, y_abajo), Mm(x2), y_pdf(plantilla, y_arriba))
+                    .with_mode(PaintMode::Fill),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+// Marquitas en las 4 esquinas, por fuera de la celda, para la guillotina
+fn dibujar_marcas(capa: &PdfLayerReference, plantilla: &Plantilla, x: f32, y: f32) {
+    capa.set_outline_color(Color::Rgb(Rgb::new(0.6, 0.6, 0.6, None)));
+    capa.set_outline_thickness(0.25 * PT_POR_MM);
+
+    let (x2, y2) = (x + plantilla.ancho_celda, y + plantilla.alto_celda);
+    let esquinas = [(x, y, -1.0, -1.0), (x2, y, 1.0, -1.0), (x, y2, -1.0, 1.0), (x2, y2, 1.0, 1.0)];
+
+    for (cx, cy, dx, dy) in esquinas {
+        for (fin_x, fin_y) in [(cx + dx * LARGO_MARCA, cy), (cx, cy + dy * LARGO_MARCA)] {
+            capa.add_line(Line {
+                points: vec![
+                    (Point::new(Mm(cx), y_pdf(plantilla, cy)), false),
+                    (Point::new(Mm(fin_x), y_pdf(plantilla, fin_y)), false),
+                ],
+                is_closed: false,
+            });
+        }
+    }
+}
@@ -0,0 +1,317 @@
+// src/jobs/mod.rs
+// Trabajos en segundo plano: la fabricación de lotes grandes no cabe en un request.
+use std::sync::{Arc, OnceLock};
+
+use futures::stream::TryStreamExt;
+use mongodb::bson::{doc, oid::ObjectId, DateTime};
+
+use crate::codes;
+use crate::pin;
+use crate::db::{self, DB};
+use crate::estados;
+use crate::models::{AlertaSeguridad, CambioEstado, EstadoIman, EstadoJob, Iman, JobLote};
+use crate::seguridad::Seguridad;
+
+// Insertamos en bloques para no mandar un insert_many gigante a Mongo
+const TAMANO_CHUNK: u32 = 500;
+
+// Si el prefijo choca con los códigos de otro lote, se cambia; más de esto ya es otra cosa
+const MAX_CAMBIOS_PREFIJO: u32 = 3;
+
+// Un job "running" sin latido en este tiempo se da por huérfano (su proceso murió)
+const LATIDO_VENCIDO_MS: i64 = 2 * 60 * 1000;
+// Cada cuánto buscamos huérfanos
+const REVISION_HUERFANOS_SEG: u64 = 60;
+
+/// Id de este proceso (uno nuevo en cada arranque)
+fn instancia() -> &'static str {
+    static ID: OnceLock<String> = OnceLock::new();
+    ID.get_or_init(|| ObjectId::new().to_hex())
+}
+
+fn hace_ms(ms: i64) -> DateTime {
+    DateTime::from_millis(DateTime::now().timestamp_millis() - ms)
+}
+
+/// Lanza la generación en una tarea de tokio. El handler regresa de inmediato.
+pub fn spawn_generacion(db: DB, job_id: ObjectId) {
+    tokio::spawn(async move {
+        if let Err(e) = ejecutar_generacion(&db, job_id).await {
+            eprintln!("❌ Job {} falló: {}", job_id, e);
+            fallar(&db, job_id, &e.to_string()).await;
+        }
+    });
+}
+
+// Marca el job como fallido y borra lo que alcanzó a insertar: un lote a medias
+// no sirve para producción y reintentarlo crea uno nuevo
+async fn fallar(db: &DB, job_id: ObjectId, error: &str) {
+    let jobs = db.collection::<JobLote>("jobs");
+    let update = doc! {
+        "$set": {
+            "estado": "failed",
+            "error": error,
+            "finished_at": DateTime::now()
+        }
+    };
+    let Ok(Some(job)) = jobs.find_one_and_update(doc! { "_id": job_id, "instancia": instancia() }, update, None).await else {
+        return;
+    };
+
+    // Solo los que siguen recién generados (nadie los ha tocado)
+    let filter = doc! { "lote_nombre": &job.lote_nombre, "lote_fecha": job.lote_fecha, "estado": "generated" };
+    match db.collection::<Iman>("imanes").delete_many(filter, None).await {
+        Ok(r) => println!("🧹 Job {}: {} imanes parciales borrados", job_id, r.deleted_count),
+        Err(e) => eprintln!("❌ Job {}: no se pudieron borrar los imanes parciales: {}", job_id, e),
+    }
+}
+
+async fn ejecutar_generacion(db: &DB, job_id: ObjectId) -> Result<(), mongodb::error::Error> {
+    let jobs = db.collection::<JobLote>("jobs");
+    let imanes = db.collection::<Iman>("imanes");
+
+    // Lo tomamos si sigue en cola o si quedó huérfano; el update atómico evita
+    // que dos procesos corran el mismo job
+    let filter = doc! {
+        "_id": job_id,
+        "$or": [
+            { "estado": "queued" },
+            { "estado": "running", "latido": { "$not": { "$gte": hace_ms(LATIDO_VENCIDO_MS) } } },
+        ]
+    };
+    let update = doc! { "$set": { "estado": "running", "instancia": instancia(), "latido": DateTime::now() } };
+    let Some(job) = jobs.find_one_and_update(filter, update, None).await? else {
+        return Ok(());
+    };
+
+    // Retomar: los inserts van en orden, así que lo que ya existe de este lote
+    // son exactamente los primeros N (el progreso guardado puede ir atrasado)
+    let existentes = imanes
+        .count_documents(doc! { "lote_nombre": &job.lote_nombre, "lote_fecha": job.lote_fecha }, None)
+        .await?;
+    let mut insertados = (existentes as u32).min(job.cantidad);
+    if insertados > 0 {
+        println!("🔁 Job {}: retomando desde {} de {}", job_id, insertados, job.cantidad);
+    }
+
+    // PIN de fábrica para la etiqueta (si hay PIN_SECRETO); en la base solo va el hash
+    let secreto = pin::secreto();
+    let mut prefijo = job.prefijo.clone();
+    let mut cambios_prefijo = 0;
+
+    while insertados < job.cantidad {
+        let fin = (insertados + TAMANO_CHUNK).min(job.cantidad);
+        let mut docs: Vec<Iman> = (insertados + 1..=fin).map(|i| {
+            // Formato: NOMBRE-UNIQUEID-NUM-X (Ej: HUASTECA-748291-0001-K)
+            // La X es el dígito verificador para detectar typos sin ir a la BD
+            let codigo = codes::con_verificador(&format!("{}-{:04}", prefijo, i));
+
+            Iman {
+                codigo,
+                lote_nombre: Some(job.lote_nombre.clone()),
+                lote_fecha: Some(job.lote_fecha),
+                estado: EstadoIman::Generated,
+                historial: vec![CambioEstado { estado: EstadoIman::Generated, at: DateTime::now(), motivo: None }],
+                ..Default::default()
+            }
+        }).collect();
+        if let Some(secreto) = &secreto {
+            con_pin_de_fabrica(&mut docs, secreto).await;
+        }
+
+        // Índice único en codigo: un repetido corta el insert (ordenado) justo ahí
+        if let Err(e) = imanes.insert_many(&docs, None).await {
+            let Some(i) = db::primer_duplicado(&e) else {
+                return Err(e);
+            };
+            let codigo = &docs[i].codigo;
+
+            // Ya existe en este mismo lote: otra instancia está corriendo el job
+            let mismo_lote = doc! { "codigo": codigo, "lote_nombre": &job.lote_nombre, "lote_fecha": job.lote_fecha };
+            if imanes.count_documents(mismo_lote, None).await? > 0 {
+                println!("⚠️ Job {}: {} ya lo insertó otra instancia, dejamos de insertar", job_id, codigo);
+                return Ok(());
+            }
+
+            // Es de otro lote (el prefijo se repitió): lo que falta sale con un prefijo nuevo.
+            // Se guarda en el job para que un reintento siga con el mismo.
+            cambios_prefijo += 1;
+            if cambios_prefijo > MAX_CAMBIOS_PREFIJO {
+                return Err(e);
+            }
+            let nuevo = codes::otro_prefijo(&prefijo);
+            println!("🔀 Job {}: {} ya existe en otro lote, seguimos con el prefijo {}", job_id, codigo, nuevo);
+            let r = jobs
+                .update_one(doc! { "_id": job_id, "instancia": instancia() }, doc! { "$set": { "prefijo": &nuevo } }, None)
+                .await?;
+            if r.matched_count == 0 {
+                return Ok(());
+            }
+            prefijo = nuevo;
+            insertados += i as u32;
+            continue;
+        }
+        insertados = fin;
+
+        // Progreso visible desde el panel, y de paso el latido. Si ya no es nuestro
+        // (nos dieron por muertos y otro proceso lo retomó), paramos aquí.
+        let r = jobs
+            .update_one(
+                doc! { "_id": job_id, "instancia": instancia() },
+                doc! { "$set": { "progreso": insertados, "latido": DateTime::now() } },
+                None,
+            )
+            .await?;
+        if r.matched_count == 0 {
+            println!("⚠️ Job {}: lo retomó otra instancia, dejamos de insertar", job_id);
+            return Ok(());
+        }
+    }
+
+    jobs.update_one(
+        doc! { "_id": job_id, "instancia": instancia() },
+        doc! { "$set": { "estado": "done", "progreso": insertados, "finished_at": DateTime::now() } },
+        None,
+    ).await?;
+
+    println!("🏭 Lote {}: {} links listos", job.lote_nombre, insertados);
+    Ok(())
+}
+
+// PBKDF2 es lento a propósito: un hash por imán, en paralelo y fuera del runtime async
+async fn con_pin_de_fabrica(docs: &mut [Iman], secreto: &str) {
+    let tareas: Vec<_> = docs
+        .iter()
+        .map(|iman| {
+            let pin = pin::de_fabrica(secreto, &iman.codigo);
+            tokio::task::spawn_blocking(move || pin::hash(&pin))
+        })
+        .collect();
+    for (iman, tarea) in docs.iter_mut().zip(tareas) {
+        // Si falla el hash, ese imán se queda sin PIN de fábrica y recibe uno al reclamarlo
+        if let Ok(hash) = tarea.await {
+            iman.owner_pin_hash = Some(hash);
+            iman.pin_de_fabrica = true;
+        }
+    }
+}
+
+/// Jobs huérfanos: en cola desde hace rato (el proceso que los encoló murió antes de
+/// arrancarlos) o "running" sin latido reciente. Al arrancar y luego cada minuto se
+/// retoman aquí; los que otra réplica está corriendo tienen latido y no se tocan.
+pub fn spawn_recuperacion(db: DB) {
+    tokio::spawn(async move {
+        let mut intervalo = tokio::time::interval(std::time::Duration::from_secs(REVISION_HUERFANOS_SEG));
+        loop {
+            intervalo.tick().await;
+            recuperar_huerfanos(&db).await;
+        }
+    });
+}
+
+async fn recuperar_huerfanos(db: &DB) {
+    let limite = hace_ms(LATIDO_VENCIDO_MS);
+    let filter = doc! {
+        "$or": [
+            { "estado": "queued", "created_at": { "$lt": limite } },
+            { "estado": "running", "latido": { "$not": { "$gte": limite } } },
+        ]
+    };
+
+    let huerfanos: Vec<JobLote> = match db.collection::<JobLote>("jobs").find(filter, None).await {
+        Ok(cursor) => cursor.try_collect().await.unwrap_or_default(),
+        Err(e) => {
+            eprintln!("❌ Error buscando jobs huérfanos: {}", e);
+            return;
+        }
+    };
+
+    for job in huerfanos {
+        if let Some(job_id) = job.id {
+            println!("⚠️ Job {} huérfano ({}), lo retomamos", job_id, etiqueta(job.estado));
+            spawn_generacion(db.clone(), job_id);
+        }
+    }
+}
+
+/// Revisión periódica de los destinos con la lista negra al día: un link que era
+/// bueno al reclamar puede dejar de serlo. Cada REVISION_SEGURIDAD_MIN minutos (60 por defecto).
+pub fn spawn_revision_seguridad(db: DB, seguridad: Arc<Seguridad>) {
+    let minutos: u64 = std::env::var("REVISION_SEGURIDAD_MIN")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|m| *m > 0)
+        .unwrap_or(60);
+
+    tokio::spawn(async move {
+        let mut intervalo = tokio::time::interval(std::time::Duration::from_secs(minutos * 60));
+        loop {
+            intervalo.tick().await;
+            seguridad.recargar().await;
+            match revisar_destinos(&db, &seguridad).await {
+                Ok(0) => {}
+                Ok(n) => println!("🚩 Revisión de seguridad: {} imanes marcados", n),
+                Err(e) => eprintln!("❌ Revisión de seguridad falló: {}", e),
+            }
+        }
+    });
+}
+
+// Regresa cuántos imanes quedaron marcados en esta pasada
+async fn revisar_destinos(db: &DB, seguridad: &Seguridad) -> Result<usize, mongodb::error::Error> {
+    let imanes = db.collection::<Iman>("imanes");
+
+    // Los que pueden mandar a alguien a algún lado (o volver a hacerlo) y aún no están marcados
+    let filter = doc! {
+        "estado": { "$in": [estados::clave(EstadoIman::Claimed), estados::clave(EstadoIman::Paused)] },
+        "alerta_seguridad": { "$exists": false },
+    };
+    let mut cursor = imanes.find(filter, None).await?;
+
+    let mut marcados = 0;
+    while let Some(iman) = cursor.try_next().await? {
+        let Some((url, motivo)) = seguridad.primera_bloqueada(iman.urls_destino()).await else {
+            continue;
+        };
+        let alerta = AlertaSeguridad { url: url.to_string(), motivo, at: DateTime::now() };
+        let Ok(alerta) = mongodb::bson::to_bson(&alerta) else {
+            continue;
+        };
+
+        // Activo -> pausado automáticamente; si ya estaba pausado solo queda la marca
+        if iman.estado == EstadoIman::Claimed {
+            let resultado = estados::transicionar(
+                db,
+                &iman.codigo,
+                EstadoIman::Paused,
+                Some(&[EstadoIman::Claimed]),
+                Some("seguridad"),
+                doc! { "alerta_seguridad": alerta },
+            )
+            .await;
+            match resultado {
+                Ok(_) => {}
+                // Cambió de estado mientras revisábamos: le toca en la próxima pasada
+                Err(estados::ErrorTransicion::NoPermitida { .. } | estados::ErrorTransicion::NoExiste) => continue,
+                Err(estados::ErrorTransicion::Db(e)) => return Err(e),
+            }
+        } else {
+            imanes
+                .update_one(doc! { "codigo": &iman.codigo }, doc! { "$set": { "alerta_seguridad": alerta } }, None)
+                .await?;
+        }
+        marcados += 1;
+    }
+
+    Ok(marcados)
+}
+
+/// Así se ve EstadoJob en las plantillas
+pub fn etiqueta(estado: EstadoJob) -> &'static str {
+    match estado {
+        EstadoJob::Queued => "En cola",
+        EstadoJob::Running => "Fabricando",
+        EstadoJob::Done => "Listo",
+        EstadoJob::Failed => "Falló",
+    }
+}
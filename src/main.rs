@@ -1,6 +1,17 @@
+mod codes;
 mod db;
+mod destinos;
+mod estados;
+mod export;
+mod hojas;
+mod jobs;
 mod models;
+mod modos;
+mod nfc;
+mod pin;
+mod qr;
 mod routes;
+mod seguridad;
 
 use axum::{routing::{get, post}, Router, response::{Redirect, IntoResponse}};
 use dotenv::dotenv;
@@ -14,6 +25,7 @@ use tower_cookies::CookieManagerLayer;
 pub struct AppState {
     pub db: db::DB,
     pub tera: Arc<Tera>, // Usamos Arc para compartirlo entre hilos
+    pub seguridad: Arc<seguridad::Seguridad>,
 }
 
 #[tokio::main]
@@ -30,6 +42,12 @@ async fn main() {
         }
     };
 
+    // Jobs que quedaron a medias (reinicio o réplica caída): se retoman
+    jobs::spawn_recuperacion(db.clone());
+
+    // Imanes de antes de la máquina de estados
+    estados::migrar(&db).await;
+
     // 2. Motor de Plantillas (Tera)
     // Busca todos los archivos en la carpeta "templates" que terminen en .html
     let tera = match Tera::new("templates/**/*.html") {
@@ -41,23 +59,64 @@ async fn main() {
     };
 
     // 3. Crear el Estado Compartido
+    let seguridad = Arc::new(seguridad::Seguridad::desde_env());
+    jobs::spawn_revision_seguridad(db.clone(), seguridad.clone());
+
     let state = AppState {
         db,
         tera: Arc::new(tera),
+        seguridad,
     };
 
     // 4. Rutas (Nota: .with_state ahora recibe 'state' completo)
     let app = Router::new()
         .route("/", get(root))
         .route("/v/:codigo", get(routes::redirect_handler))
+        .route("/v/:codigo/dueno", get(routes::dueno_page))
+        .route("/v/:codigo/l/:indice", get(routes::landing_clic))
+        .route("/reportar", get(routes::reportar_page))
+        .route("/api/reportar", post(routes::reportar))
         .route("/api/setup", post(routes::save_iman))
+        .route("/api/dueno/:codigo", post(routes::dueno_accion))
+        .route("/api/dueno/:codigo/programacion", post(routes::dueno_agregar_programacion))
+        .route("/api/dueno/:codigo/programacion/:id/borrar", post(routes::dueno_borrar_programacion))
         .route("/login", get(routes::login_page))
         .route("/auth/login", post(routes::process_login))
         .route("/admin", get(routes::admin_dashboard))
         .route("/api/admin/generate", post(routes::generate_batch))
+        .route("/admin/jobs/:id", get(routes::job_status))
         .route("/auth/logout", get(routes::logout))
         .route("/api/admin/export", get(routes::export_csv))
-        .route("/api/csv/:lote_nombre/:tipo", get(routes::export_csv_lote))
+        .route("/admin/exportaciones", get(routes::exportaciones_page))
+        .route("/admin/exportaciones/:id/csv", get(routes::redescargar_exportacion))
+        .route("/api/admin/exportaciones/:id/desmarcar", post(routes::desmarcar_exportacion))
+        .route("/admin/reportes", get(routes::reportes_page))
+        .route("/api/admin/reportes/:codigo/resolver", post(routes::resolver_reportes))
+        .route("/admin/importar", get(routes::importar_page))
+        .route(
+            "/api/admin/importar",
+            // Archivos de hasta 10 MB (el default de axum es 2 MB)
+            post(routes::importar_csv).layer(axum::extract::DefaultBodyLimit::max(10 * 1024 * 1024)),
+        )
+        .route("/admin/imanes/:codigo", get(routes::iman_page))
+        .route("/api/admin/imanes/:codigo/estado", post(routes::cambiar_estado_iman))
+        .route("/api/admin/imanes/:codigo/programacion", post(routes::agregar_programacion))
+        .route("/api/admin/imanes/:codigo/programacion/:id/borrar", post(routes::borrar_programacion))
+        .route("/api/admin/imanes/:codigo/reglas", post(routes::agregar_regla))
+        .route("/api/admin/imanes/:codigo/reglas/:id/borrar", post(routes::borrar_regla))
+        .route("/api/admin/imanes/:codigo/variantes", post(routes::agregar_variante))
+        .route("/api/admin/imanes/:codigo/utm", post(routes::guardar_utm))
+        .route("/api/admin/imanes/:codigo/alerta/borrar", post(routes::quitar_alerta))
+        .route("/api/admin/imanes/:codigo/variantes/:id/borrar", post(routes::borrar_variante))
+        .route("/api/admin/lotes/:lote_nombre/estado", post(routes::cambiar_estado_lote))
+        .route("/admin/lotes/:lote_nombre", get(routes::lote_page))
+        .route("/api/admin/lotes/:lote_nombre/config", post(routes::guardar_config_lote))
+        .route("/api/csv/:lote_nombre/:tipo", get(routes::export_lote)) // Ruta vieja, sigue funcionando
+        .route("/api/export/:lote_nombre/:tipo", get(routes::export_lote))
+        .route("/api/nfc/:lote_nombre/:tipo/:formato", get(routes::export_nfc_lote))
+        .route("/api/admin/qr/:codigo", get(routes::qr_iman))
+        .route("/api/hoja/:lote_nombre/:tipo", get(routes::hoja_qr_lote))
+        .route("/api/etiquetas/:lote_nombre/:tipo", get(routes::etiquetas_lote))
         .layer(CookieManagerLayer::new()) // ¡Activa cookies!
         .layer(axum::middleware::from_fn(no_cache_headers)) //MIDDLEWARE
         .with_state(state);
@@ -70,7 +129,8 @@ async fn main() {
     println!("🚀 Servidor corriendo en http://{}", addr);
     
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    // ConnectInfo: la IP de la conexión, para los reportes de abuso cuando no hay proxy enfrente
+    axum::serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>()).await.unwrap();
 }
 
 async fn root() -> Redirect {
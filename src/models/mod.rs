@@ -1,8 +1,10 @@
 use serde::{Deserialize, Serialize};
 use mongodb::bson::oid::ObjectId;
-use mongodb::bson::DateTime;
+use mongodb::bson::{doc, DateTime, Document};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+use crate::estados;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct Iman {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
@@ -20,9 +22,446 @@ pub struct Iman {
     #[serde(default)] 
     pub exported: bool, 
 
+    // Exportación (colección "exportaciones") en la que salió este imán
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub export_id: Option<ObjectId>,
+
+    // PIN del propietario en texto plano: solo imanes viejos, se migra al hash en el primer uso
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner_pin: Option<String>,
+
+    // PIN del propietario con hash y sal (ver pin::hash)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner_pin_hash: Option<String>,
+
+    // El hash es del PIN de fábrica (pin::de_fabrica): se puede imprimir en la etiqueta
+    #[serde(default)]
+    pub pin_de_fabrica: bool,
+
+    // Intentos fallidos de PIN seguidos y cuándo fue el último (bloqueo por imán)
+    #[serde(default)]
+    pub pin_fallos: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pin_fallo_at: Option<DateTime>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub lote_nombre: Option<String>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub lote_fecha: Option<DateTime>,
+
+    // Estado explícito del ciclo de vida. Solo se cambia con crate::estados
+    // (`active` queda como espejo de `estado == Claimed` para lo que ya lo lee)
+    #[serde(default)]
+    pub estado: EstadoIman,
+
+    // Cada cambio de estado con su fecha, en orden
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub historial: Vec<CambioEstado>,
+
+    // Destinos con fecha (promo de diciembre, etc.). Ver destinos::url_vigente
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub programacion: Vec<DestinoProgramado>,
+
+    // Destinos alternos por teléfono o idioma (App Store vs Play Store, etc.)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reglas: Vec<ReglaDestino>,
+
+    // Prueba A/B: si hay variantes, sustituyen al target_url (cada visitante se queda con la suya)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variantes: Vec<Variante>,
+
+    // Parámetros UTM para el redirect (ver destinos::con_utm). Si falta, se usa el del lote
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub utm: Option<String>,
+
+    // Puesta por la revisión de seguridad (crate::seguridad); mientras exista, el dueño no puede reactivarlo
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alerta_seguridad: Option<AlertaSeguridad>,
+
+    // Qué pasa al escanear (ver crate::modos)
+    #[serde(default)]
+    pub modo: ModoIman,
+
+    // Solo en modo Landing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub landing: Option<Landing>,
+
+    // Solo en modo Vcard
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contacto: Option<Contacto>,
+
+    // Solo en modo Wifi. La contraseña nunca sale en el panel ni en exportaciones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wifi: Option<Wifi>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ModoIman {
+    // Redirige a target_url (o a la programación vigente)
+    #[default]
+    Redireccion,
+    // Página propia con varios links ("link in bio")
+    Landing,
+    // Descarga un .vcf para agregar el contacto (tarjetas de presentación)
+    Vcard,
+    // Muestra la red WiFi y su QR para conectarse (hoteles, Airbnbs)
+    Wifi,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Landing {
+    pub titulo: String,
+    // Un emoji
+    pub avatar: String,
+    pub enlaces: Vec<EnlaceLanding>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Contacto {
+    pub nombre: String,
+    pub telefono: Option<String>,
+    pub email: Option<String>,
+    pub empresa: Option<String>,
+    #[serde(default)]
+    pub enlaces: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Wifi {
+    pub ssid: String,
+    // Vacía en redes abiertas
+    pub password: String,
+    // WPA (incluye WPA2/WPA3), WEP o nopass, como lo pide el formato WIFI: del QR
+    pub seguridad: String,
+    #[serde(default)]
+    pub oculta: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EnlaceLanding {
+    // instagram, tiktok, whatsapp... (ver modos::REDES)
+    pub red: String,
+    pub etiqueta: String,
+    pub url: String,
+    #[serde(default)]
+    pub clics: u32,
+}
+
+// Destino alterno según quién escanea. Las condiciones que vengan deben cumplirse todas.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReglaDestino {
+    pub id: ObjectId,
+
+    // None = cualquier teléfono
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub so: Option<SistemaOperativo>,
+
+    // Idioma principal del teléfono, ISO 639-1 ("en", "es"). None = cualquiera
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idioma: Option<String>,
+
+    pub url: String,
+
+    // Abre la app directo (instagram://...); si no está instalada, cae a `url`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deep_link: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AlertaSeguridad {
+    // El destino que salió bloqueado
+    pub url: String,
+    pub motivo: String,
+    pub at: DateTime,
+}
+
+// Reporte de abuso de alguien que escaneó el imán (colección "reportes")
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Reporte {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub codigo: String,
+    // phishing, malware, spam, inapropiado u otro (ver routes::reportes::MOTIVOS)
+    pub motivo: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comentario: Option<String>,
+    // A dónde mandaba el imán cuando lo reportaron
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    // HMAC de IP + código: una persona cuenta una sola vez por imán (no guardamos la IP)
+    pub huella: String,
+    // HMAC de la IP sola, para limitar cuántos reportes manda por hora
+    #[serde(default)]
+    pub red: String,
+    pub estado: EstadoReporte,
+    pub at: DateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EstadoReporte {
+    Pendiente,
+    Descartado,
+    Confirmado,
+}
+
+// Configuración que comparten todos los imanes de un lote (colección "lotes_config").
+// Va por nombre y fecha, igual que el lote: dos lotes con el mismo nombre no la comparten.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ConfigLote {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub lote_nombre: String,
+
+    // Sin fecha: configuración de antes, aplica a todos los lotes con ese nombre
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lote_fecha: Option<DateTime>,
+
+    // Plantilla UTM ("utm_source=iman&utm_campaign={lote}") para los imanes sin una propia
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub utm: Option<String>,
+
+    // Cómo se manda al destino (código HTTP o página intermedia)
+    #[serde(default)]
+    pub redireccion: TipoRedireccion,
+
+    // Cuánto se muestra la página intermedia antes de seguir (milisegundos)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub intersticial_ms: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TipoRedireccion {
+    // 302 Found: el más compatible con rastreadores viejos
+    #[serde(rename = "302")]
+    Found,
+    // 307 Temporary Redirect: lo de siempre
+    #[default]
+    #[serde(rename = "307")]
+    Temporal,
+    // 308 Permanent Redirect: el navegador puede recordarlo y saltarse los siguientes escaneos
+    // (solo para destinos que no cambian; con programación, reglas o A/B se usa 307)
+    #[serde(rename = "308")]
+    Permanente,
+    // Página con la marca unos segundos y un botón de "continuar"
+    #[serde(rename = "intersticial")]
+    Intersticial,
+}
+
+// Una opción de la prueba A/B. La probabilidad de salir es peso / suma de pesos.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Variante {
+    pub id: ObjectId,
+    pub nombre: String,
+    pub url: String,
+    pub peso: u32,
+    // Escaneos que cayeron en esta variante
+    #[serde(default)]
+    pub visitas: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SistemaOperativo {
+    Ios,
+    Android,
+}
+
+// Un destino que solo aplica entre `inicio` y `fin` (sin límite si falta alguno)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DestinoProgramado {
+    pub id: ObjectId,
+    pub url: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inicio: Option<DateTime>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fin: Option<DateTime>,
+}
+
+// Ciclo de vida de un imán: fábrica -> grabado -> venta -> dueño
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum EstadoIman {
+    #[default]
+    Generated,
+    Exported,
+    Encoded,
+    Sold,
+    Claimed,
+    Paused,
+    Disabled,
+    Lost,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CambioEstado {
+    pub estado: EstadoIman,
+    pub at: DateTime,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub motivo: Option<String>,
+}
+
+// Agrupación de EstadoIman para exportar y contar por lote.
+// Se deduce del estado y las visitas, no es un campo guardado.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EstadoUso {
+    // Nunca escaneado ni configurado: se puede grabar/vender tranquilo
+    Virgen,
+    // Alguien lo escaneó pero nunca lo reclamó (no tiene destino)
+    Escaneado,
+    // Reclamado y redirigiendo
+    Activo,
+    // Reclamado pero apagado (pausado, deshabilitado o perdido)
+    Desactivado,
+}
+
+impl EstadoUso {
+    pub const TODOS: [EstadoUso; 4] = [EstadoUso::Virgen, EstadoUso::Escaneado, EstadoUso::Activo, EstadoUso::Desactivado];
+
+    /// El `tipo` de las rutas de exportación. "available" era el nombre viejo de "virgin".
+    pub fn parse(tipo: &str) -> Option<EstadoUso> {
+        match tipo {
+            "virgin" | "available" => Some(EstadoUso::Virgen),
+            "scanned" => Some(EstadoUso::Escaneado),
+            "active" => Some(EstadoUso::Activo),
+            "disabled" => Some(EstadoUso::Desactivado),
+            _ => None,
+        }
+    }
+
+    pub fn clave(self) -> &'static str {
+        match self {
+            EstadoUso::Virgen => "virgin",
+            EstadoUso::Escaneado => "scanned",
+            EstadoUso::Activo => "active",
+            EstadoUso::Desactivado => "disabled",
+        }
+    }
+
+    /// El mismo criterio que `Iman::estado_uso`, pero como filtro de Mongo
+    pub fn filtro(self) -> Document {
+        use EstadoIman::*;
+        let (estados, visitas) = match self {
+            EstadoUso::Virgen => (&estados::SIN_DUENO[..], Some(doc! { "visitas": 0 })),
+            EstadoUso::Escaneado => (&estados::SIN_DUENO[..], Some(doc! { "visitas": { "$gt": 0 } })),
+            EstadoUso::Activo => (&[Claimed][..], None),
+            EstadoUso::Desactivado => (&[Paused, Disabled, Lost][..], None),
+        };
+
+        let mut filtro = estados::filtro_estados(estados);
+        filtro.extend(visitas.unwrap_or_default());
+        filtro
+    }
+}
+
+impl Iman {
+    pub fn estado_uso(&self) -> EstadoUso {
+        match self.estado {
+            EstadoIman::Claimed => EstadoUso::Activo,
+            EstadoIman::Paused | EstadoIman::Disabled | EstadoIman::Lost => EstadoUso::Desactivado,
+            _ if self.visitas > 0 => EstadoUso::Escaneado,
+            _ => EstadoUso::Virgen,
+        }
+    }
+
+    /// ¿Tiene a dónde mandar según su modo? Un imán activo sin esto muestra el setup pero ya no se puede reclamar.
+    pub fn tiene_destino(&self) -> bool {
+        match self.modo {
+            ModoIman::Redireccion => self.target_url.as_deref().is_some_and(|u| !u.is_empty()),
+            ModoIman::Landing => self.landing.is_some(),
+            ModoIman::Vcard => self.contacto.is_some(),
+            ModoIman::Wifi => self.wifi.is_some(),
+        }
+    }
+
+    /// Todas las URLs a las que este imán puede mandar a alguien (para revisarlas)
+    pub fn urls_destino(&self) -> Vec<&str> {
+        let mut urls: Vec<&str> = self.target_url.as_deref().into_iter().collect();
+        if let Some(landing) = &self.landing {
+            urls.extend(landing.enlaces.iter().map(|e| e.url.as_str()));
+        }
+        if let Some(contacto) = &self.contacto {
+            urls.extend(contacto.enlaces.iter().map(String::as_str));
+        }
+        urls.extend(self.programacion.iter().map(|p| p.url.as_str()));
+        urls.extend(self.reglas.iter().map(|r| r.url.as_str()));
+        urls.extend(self.variantes.iter().map(|v| v.url.as_str()));
+        urls.retain(|u| !u.is_empty());
+        urls
+    }
+}
+
+// Estado de un trabajo en segundo plano
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EstadoJob {
+    Queued,
+    Running,
+    Done,
+    Failed,
+}
+
+// Registro de la generación de un lote (colección "jobs")
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct JobLote {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub estado: EstadoJob,
+    pub lote_nombre: String,
+    pub lote_fecha: DateTime,
+    // Parte fija de los códigos (NOMBRE-UNIQUEID), se decide al encolar
+    pub prefijo: String,
+    pub cantidad: u32,
+    pub progreso: u32,
+
+    // Qué proceso lo está corriendo y cuándo dio señales de vida por última vez
+    // (con varias réplicas, un job sin latido reciente se retoma en otra)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instancia: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latido: Option<DateTime>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    pub created_at: DateTime,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<DateTime>,
+}
+
+// Una submission ya procesada de "Fabricar Lote" (colección "idempotencia").
+// Mongo la borra sola al terminar la ventana gracias al índice TTL.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ClaveIdempotencia {
+    pub key: String,
+    pub job_id: ObjectId,
+    pub created_at: DateTime,
+}
+
+// Un archivo de "exportar nuevos" ya entregado (colección "exportaciones").
+// Guarda exactamente qué códigos salieron para poder re-descargarlo o deshacerlo.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Exportacion {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub created_at: DateTime,
+    // El nombre que escribió el operador al entrar (la contraseña es compartida):
+    // sirve de referencia, no como auditoría
+    pub usuario: String,
+
+    // Los códigos no se guardan aquí (un lote grande rebasaría los 16 MB de un documento):
+    // son los imanes con este export_id
+    pub total: u32,
+
+    #[serde(default)]
+    pub revertida: bool,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revertida_at: Option<DateTime>,
 }
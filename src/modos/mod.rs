@@ -0,0 +1,238 @@
+// src/modos/mod.rs
+// Qué hace un imán al escanearlo: redirigir (lo de siempre) o mostrar una
+// página propia. Aquí viven la validación y el armado de cada modo.
+use crate::destinos;
+use crate::models::{Contacto, EnlaceLanding, Landing, Wifi};
+
+// Máximo de caracteres del título de la landing
+const MAX_TITULO: usize = 60;
+
+/// Las redes que ofrece el formulario de setup, en el orden en que se muestran
+pub const REDES: [(&str, &str); 5] = [
+    ("instagram", "Instagram"),
+    ("tiktok", "TikTok"),
+    ("whatsapp", "WhatsApp"),
+    ("youtube", "YouTube"),
+    ("facebook", "Facebook"),
+];
+
+/// Convierte lo que escribió el usuario ("@usuario", "5512345678", un link...) en el link final
+pub fn enlace_red(red: &str, valor: &str) -> Result<String, &'static str> {
+    let valor = valor.trim();
+    let usuario = valor.trim_start_matches('@');
+    let es_usuario = !usuario.is_empty()
+        && usuario.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_'));
+
+    let url = match red {
+        "instagram" if es_usuario => format!("https://instagram.com/{}", usuario),
+        "tiktok" if es_usuario => format!("https://tiktok.com/@{}", usuario),
+        "whatsapp" => {
+            // El link de wa.me lo armamos nosotros: solo aceptamos el número
+            let digitos: String = valor.chars().filter(char::is_ascii_digit).collect();
+            if !(10..=15).contains(&digitos.len()) {
+                return Err("⚠️ El WhatsApp debe ser un número de 10 a 15 dígitos (con lada).");
+            }
+            return Ok(format!("https://wa.me/{}", digitos));
+        }
+        _ => valor.to_string(),
+    };
+
+    destinos::validar_destino(&url)?;
+    Ok(url)
+}
+
+/// Arma la landing desde el formulario. `redes` son pares (red, lo que escribió el usuario).
+pub fn armar_landing<'a>(
+    titulo: &str,
+    avatar: &str,
+    redes: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> Result<Landing, &'static str> {
+    let titulo = titulo.trim();
+    if titulo.is_empty() || titulo.chars().count() > MAX_TITULO {
+        return Err("⚠️ Ponle un título a tu página (máximo 60 caracteres).");
+    }
+
+    // Un emoji puede ocupar varios `char` (banderas, tonos de piel), pero no un texto largo
+    let avatar = avatar.trim();
+    let avatar = if avatar.is_empty() { "✨" } else { avatar };
+    if avatar.chars().count() > 8 {
+        return Err("⚠️ El avatar debe ser un emoji.");
+    }
+
+    let mut enlaces = Vec::new();
+    for (red, valor) in redes {
+        if valor.trim().is_empty() {
+            continue;
+        }
+        let etiqueta = REDES.iter().find(|(clave, _)| *clave == red).map(|(_, e)| *e).unwrap_or(red);
+        enlaces.push(EnlaceLanding {
+            red: red.to_string(),
+            etiqueta: etiqueta.to_string(),
+            url: enlace_red(red, valor)?,
+            clics: 0,
+        });
+    }
+
+    if enlaces.is_empty() {
+        return Err("⚠️ Agrega al menos un link.");
+    }
+
+    Ok(Landing { titulo: titulo.to_string(), avatar: avatar.to_string(), enlaces })
+}
+
+// Campos de texto opcionales: vacío es lo mismo que no venir
+fn opcional(valor: &str) -> Option<String> {
+    Some(valor.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+/// Arma la tarjeta de contacto desde el formulario. `enlaces` son links (sitio web) o redes como en la landing.
+pub fn armar_contacto<'a>(
+    nombre: &str,
+    telefono: &str,
+    email: &str,
+    empresa: &str,
+    enlaces: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> Result<Contacto, &'static str> {
+    let nombre = nombre.trim();
+    if nombre.is_empty() || nombre.chars().count() > MAX_TITULO {
+        return Err("⚠️ Escribe tu nombre (máximo 60 caracteres).");
+    }
+
+    let telefono = opcional(telefono);
+    if let Some(tel) = &telefono
+        && (!tel.chars().all(|c| c.is_ascii_digit() || " +-()".contains(c))
+            || !(8..=15).contains(&tel.chars().filter(char::is_ascii_digit).count()))
+    {
+        return Err("⚠️ El teléfono solo puede llevar números, espacios, + y guiones.");
+    }
+
+    let email = opcional(email);
+    if let Some(correo) = &email
+        && (correo.len() > 254
+            || correo.chars().any(char::is_whitespace)
+            || !correo.split_once('@').is_some_and(|(u, d)| !u.is_empty() && d.contains('.')))
+    {
+        return Err("⚠️ El correo no parece válido.");
+    }
+
+    if telefono.is_none() && email.is_none() {
+        return Err("⚠️ Agrega al menos un teléfono o un correo.");
+    }
+
+    let mut links = Vec::new();
+    for (red, valor) in enlaces {
+        if valor.trim().is_empty() {
+            continue;
+        }
+        // El sitio web va tal cual (no pasa por redirección, solo queda guardado en el contacto)
+        let url = if red == "sitio" {
+            let url = valor.trim();
+            if !url.starts_with("https://") || url::Url::parse(url).is_err() {
+                return Err("⚠️ El sitio web debe ser un enlace https:// válido.");
+            }
+            url.to_string()
+        } else {
+            enlace_red(red, valor)?
+        };
+        links.push(url);
+    }
+
+    Ok(Contacto { nombre: nombre.to_string(), telefono, email, empresa: opcional(empresa), enlaces: links })
+}
+
+// Escapa un valor de texto según RFC 6350 (diagonal invertida, coma, punto y coma, saltos de línea)
+fn escapar_vcard(valor: &str) -> String {
+    let mut salida = String::with_capacity(valor.len());
+    for c in valor.chars() {
+        match c {
+            '\\' => salida.push_str("\\\\"),
+            ',' => salida.push_str("\\,"),
+            ';' => salida.push_str("\\;"),
+            '\n' => salida.push_str("\\n"),
+            '\r' => {}
+            _ => salida.push(c),
+        }
+    }
+    salida
+}
+
+/// El .vcf que se descarga al escanear. vCard 3.0: la que mejor abren iOS y Android.
+pub fn vcard(contacto: &Contacto) -> String {
+    let nombre = escapar_vcard(&contacto.nombre);
+    let mut lineas = vec![
+        "BEGIN:VCARD".to_string(),
+        "VERSION:3.0".to_string(),
+        format!("FN:{}", nombre),
+        // N es obligatorio en 3.0; no partimos nombre y apellido, va completo como apellido
+        format!("N:{};;;;", nombre),
+    ];
+    if let Some(empresa) = &contacto.empresa {
+        lineas.push(format!("ORG:{}", escapar_vcard(empresa)));
+    }
+    if let Some(tel) = &contacto.telefono {
+        lineas.push(format!("TEL;TYPE=CELL:{}", escapar_vcard(tel)));
+    }
+    if let Some(email) = &contacto.email {
+        lineas.push(format!("EMAIL;TYPE=INTERNET:{}", escapar_vcard(email)));
+    }
+    for url in &contacto.enlaces {
+        lineas.push(format!("URL:{}", escapar_vcard(url)));
+    }
+    lineas.push("END:VCARD".to_string());
+
+    // El estándar pide CRLF
+    lineas.join("\r\n") + "\r\n"
+}
+
+/// Arma la red WiFi desde el formulario. `seguridad` llega como WPA, WEP o nopass.
+pub fn armar_wifi(ssid: &str, password: &str, seguridad: &str, oculta: bool) -> Result<Wifi, &'static str> {
+    // El SSID puede llevar espacios a propósito: no lo recortamos, solo revisamos que no esté vacío
+    if ssid.trim().is_empty() || ssid.len() > 32 {
+        return Err("⚠️ El nombre de la red debe tener de 1 a 32 caracteres.");
+    }
+
+    let (seguridad, valida) = match seguridad {
+        "WPA" => ("WPA", (8..=63).contains(&password.len())),
+        "WEP" => ("WEP", matches!(password.len(), 5 | 10 | 13 | 26)),
+        "nopass" => ("nopass", true),
+        _ => return Err("⚠️ Tipo de seguridad no válido."),
+    };
+    if !valida {
+        return Err(if seguridad == "WEP" {
+            "⚠️ Una contraseña WEP tiene 5 o 13 caracteres (10 o 26 en hexadecimal)."
+        } else {
+            "⚠️ Una contraseña WPA tiene de 8 a 63 caracteres."
+        });
+    }
+
+    Ok(Wifi {
+        ssid: ssid.to_string(),
+        password: if seguridad == "nopass" { String::new() } else { password.to_string() },
+        seguridad: seguridad.to_string(),
+        oculta,
+    })
+}
+
+// En el formato WIFI: hay que escapar la diagonal invertida, ; , : y comillas
+fn escapar_wifi(valor: &str) -> String {
+    let mut salida = String::with_capacity(valor.len());
+    for c in valor.chars() {
+        if matches!(c, '\\' | ';' | ',' | ':' | '"') {
+            salida.push('\\');
+        }
+        salida.push(c);
+    }
+    salida
+}
+
+/// Texto del QR que iOS y Android reconocen para unirse a la red
+pub fn wifi_qr(wifi: &Wifi) -> String {
+    let mut texto = format!("WIFI:T:{};S:{};", wifi.seguridad, escapar_wifi(&wifi.ssid));
+    if wifi.seguridad != "nopass" {
+        texto.push_str(&format!("P:{};", escapar_wifi(&wifi.password)));
+    }
+    if wifi.oculta {
+        texto.push_str("H:true;");
+    }
+    texto + ";"
+}
@@ -0,0 +1,173 @@
+// src/nfc/mod.rs
+// Registros NDEF listos para grabar en los tags (NFC Forum URI RTD).
+use std::io::Write;
+
+// Prefijos de URI abreviados (tabla oficial del URI Record Type Definition).
+// El índice en la tabla es el byte que va al inicio del payload.
+const PREFIJOS_URI: [&str; 36] = [
+    "",
+    "http://www.",
+    "https://www.",
+    "http://",
+    "https://",
+    "tel:",
+    "mailto:",
+    "ftp://anonymous:anonymous@",
+    "ftp://ftp.",
+    "ftps://",
+    "sftp://",
+    "smb://",
+    "nfs://",
+    "ftp://",
+    "dav://",
+    "news:",
+    "telnet://",
+    "imap:",
+    "rtsp://",
+    "urn:",
+    "pop:",
+    "sip:",
+    "sips:",
+    "tftp:",
+    "btspp://",
+    "btl2cap://",
+    "btgoep://",
+    "tcpobex://",
+    "irdaobex://",
+    "file://",
+    "urn:epc:id:",
+    "urn:epc:tag:",
+    "urn:epc:pat:",
+    "urn:epc:raw:",
+    "urn:epc:",
+    "urn:nfc:",
+];
+
+// Bits del header del registro NDEF
+const MB: u8 = 0x80; // Message Begin
+const ME: u8 = 0x40; // Message End
+const SR: u8 = 0x10; // Short Record (payload < 256 bytes)
+const TNF_WELL_KNOWN: u8 = 0x01;
+
+/// Busca el prefijo más largo que aplique: "https://" -> 0x04, "https://www." -> 0x02
+fn comprimir_uri(url: &str) -> (u8, &str) {
+    PREFIJOS_URI
+        .iter()
+        .enumerate()
+        .skip(1)
+        .filter(|(_, p)| url.starts_with(*p))
+        .max_by_key(|(_, p)| p.len())
+        .map(|(i, p)| (i as u8, &url[p.len()..]))
+        .unwrap_or((0x00, url))
+}
+
+/// Mensaje NDEF con un solo registro URI (lo que escribe NFC Tools al grabar un link)
+pub fn mensaje_uri(url: &str) -> Vec<u8> {
+    let (codigo_prefijo, resto) = comprimir_uri(url);
+
+    let mut payload = Vec::with_capacity(resto.len() + 1);
+    payload.push(codigo_prefijo);
+    payload.extend_from_slice(resto.as_bytes());
+
+    let mut mensaje = Vec::with_capacity(payload.len() + 7);
+    if payload.len() < 256 {
+        mensaje.push(MB | ME | SR | TNF_WELL_KNOWN);
+        mensaje.push(1); // Largo del tipo
+        mensaje.push(payload.len() as u8);
+    } else {
+        mensaje.push(MB | ME | TNF_WELL_KNOWN);
+        mensaje.push(1);
+        mensaje.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    }
+    mensaje.push(b'U'); // Tipo: URI
+    mensaje.extend(payload);
+    mensaje
+}
+
+/// El mensaje en hexadecimal (para codificadoras masivas que piden el NDEF crudo)
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+/// Un ZIP con un archivo `CODIGO.ndef` por imán: (codigo, url)
+pub fn zip_ndef(registros: Vec<(String, String)>) -> zip::result::ZipResult<Vec<u8>> {
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let opciones = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+
+    for (codigo, url) in registros {
+        zip.start_file(format!("{}.ndef", codigo), opciones)?;
+        zip.write_all(&mensaje_uri(&url))?;
+    }
+
+    Ok(zip.finish()?.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registro_uri_https() {
+        let mensaje = mensaje_uri("https://instagram.com/x");
+        let mut esperado = vec![
+            0xD1, // MB | ME | SR | TNF well-known
+            0x01, // largo del tipo
+            0x10, // largo del payload: prefijo + "instagram.com/x"
+            b'U', // tipo URI
+            0x04, // "https://"
+        ];
+        esperado.extend_from_slice(b"instagram.com/x");
+        assert_eq!(mensaje, esperado);
+        assert_eq!(hex(&mensaje[..5]), "D101105504");
+    }
+
+    #[test]
+    fn gana_el_prefijo_mas_largo() {
+        assert_eq!(comprimir_uri("https://www.ejemplo.com"), (0x02, "ejemplo.com"));
+        assert_eq!(comprimir_uri("http://ejemplo.com"), (0x03, "ejemplo.com"));
+        assert_eq!(comprimir_uri("tel:5512345678"), (0x05, "5512345678"));
+        assert_eq!(comprimir_uri("urn:epc:id:sgtin:1"), (0x1E, "sgtin:1"));
+        // Sin prefijo conocido va completo con 0x00
+        assert_eq!(comprimir_uri("geo:19.4,-99.1"), (0x00, "geo:19.4,-99.1"));
+    }
+
+    #[test]
+    fn registro_largo_sin_sr() {
+        let url = format!("https://ejemplo.com/{}", "a".repeat(300));
+        let mensaje = mensaje_uri(&url);
+        let largo_payload = 1 + url.len() - "https://".len();
+
+        // Sin SR el largo va en 4 bytes big-endian
+        assert_eq!(mensaje[0], 0xC1);
+        assert_eq!(mensaje[1], 0x01);
+        assert_eq!(mensaje[2..6], (largo_payload as u32).to_be_bytes());
+        assert_eq!(mensaje[6], b'U');
+        assert_eq!(mensaje[7], 0x04);
+        assert_eq!(mensaje.len(), 7 + largo_payload);
+    }
+
+    #[test]
+    fn limite_del_registro_corto() {
+        // 255 bytes de payload todavía caben en SR; 256 ya no
+        let corto = format!("https://{}", "a".repeat(254));
+        assert_eq!(mensaje_uri(&corto)[..3], [0xD1, 0x01, 0xFF]);
+        let largo = format!("https://{}", "a".repeat(255));
+        assert_eq!(mensaje_uri(&largo)[0], 0xC1);
+    }
+
+    #[test]
+    fn zip_con_un_ndef_por_codigo() {
+        let registros = vec![
+            ("A-0001-X".to_string(), "https://imanes.mx/v/A-0001-X".to_string()),
+            ("A-0002-Y".to_string(), "https://imanes.mx/v/A-0002-Y".to_string()),
+        ];
+        let bytes = zip_ndef(registros).unwrap();
+
+        let mut zip = zip::ZipArchive::new(std::io::Cursor::new(bytes)).unwrap();
+        assert_eq!(zip.len(), 2);
+        let mut contenido = Vec::new();
+        std::io::Read::read_to_end(&mut zip.by_name("A-0002-Y.ndef").unwrap(), &mut contenido).unwrap();
+        assert_eq!(contenido, mensaje_uri("https://imanes.mx/v/A-0002-Y"));
+    }
+}
@@ -0,0 +1,85 @@
+// src/pin/mod.rs
+// PIN del dueño: hash para guardarlo y el "PIN de fábrica" que sale impreso en la etiqueta.
+//
+// El PIN de fábrica se deriva del código con un secreto del servidor (PIN_SECRETO):
+// así la etiqueta se puede imprimir (o reimprimir) cuando sea sin guardar el PIN en
+// claro. En la base solo queda su hash. Sin PIN_SECRETO no hay PIN de fábrica y el
+// dueño recibe uno al reclamar; con él, al reclamar ya no se muestra ninguno, así que
+// actívalo solo si los imanes salen con su etiqueta de empaque.
+// OJO: cambiar PIN_SECRETO hace que las etiquetas que se impriman después ya no
+// coincidan con los imanes ya fabricados.
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+// PBKDF2-SHA256: lento a propósito, por si alguien se lleva la base
+const RONDAS_PIN: u32 = 100_000;
+
+fn derivar(pin: &str, sal: &[u8], rondas: u32) -> [u8; 32] {
+    let mut salida = [0u8; 32];
+    pbkdf2::pbkdf2::<Hmac<Sha256>>(pin.as_bytes(), sal, rondas, &mut salida);
+    salida
+}
+
+/// Lo que se guarda en `owner_pin_hash`: "pbkdf2-sha256$rondas$sal$hash" (hex)
+pub fn hash(pin: &str) -> String {
+    let sal: [u8; 16] = rand::random();
+    let hash = derivar(pin, &sal, RONDAS_PIN);
+    format!("pbkdf2-sha256${}${}${}", RONDAS_PIN, hex::encode(sal), hex::encode(hash))
+}
+
+/// ¿`pin` corresponde al hash guardado?
+pub fn coincide(pin: &str, guardado: &str) -> bool {
+    let partes: Vec<&str> = guardado.split('$').collect();
+    let ["pbkdf2-sha256", rondas, sal, hash] = partes[..] else {
+        return false;
+    };
+    let (Ok(rondas), Ok(sal), Ok(hash)) = (rondas.parse(), hex::decode(sal), hex::decode(hash)) else {
+        return false;
+    };
+    let calculado = derivar(pin, &sal, rondas);
+    // Comparación de tiempo constante
+    hash.len() == calculado.len() && hash.iter().zip(calculado).fold(0, |dif, (a, b)| dif | (a ^ b)) == 0
+}
+
+/// Un PIN nuevo al azar (6 dígitos)
+pub fn aleatorio() -> String {
+    format!("{:06}", rand::random_range(0..1_000_000))
+}
+
+/// El secreto para los PINs de fábrica (None = no se generan)
+pub fn secreto() -> Option<String> {
+    std::env::var("PIN_SECRETO").ok().filter(|s| !s.is_empty())
+}
+
+/// PIN de fábrica de un código: siempre el mismo para el mismo secreto
+pub fn de_fabrica(secreto: &str, codigo: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secreto.as_bytes()).expect("HMAC acepta llaves de cualquier largo");
+    mac.update(b"pin\0");
+    mac.update(codigo.as_bytes());
+    let bytes = mac.finalize().into_bytes();
+    let numero = u64::from_be_bytes(bytes[..8].try_into().expect("8 bytes"));
+    format!("{:06}", numero % 1_000_000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_y_coincide() {
+        let guardado = hash("123456");
+        assert!(coincide("123456", &guardado));
+        assert!(!coincide("123457", &guardado));
+        assert!(!coincide("123456", "texto-plano"));
+    }
+
+    #[test]
+    fn pin_de_fabrica_estable_por_codigo_y_secreto() {
+        let pin = de_fabrica("secreto", "HUASTECA-748291-0001-E");
+        assert_eq!(pin.len(), 6);
+        assert!(pin.chars().all(|c| c.is_ascii_digit()));
+        assert_eq!(pin, de_fabrica("secreto", "HUASTECA-748291-0001-E"));
+        assert_ne!(pin, de_fabrica("otro", "HUASTECA-748291-0001-E"));
+        assert_ne!(pin, de_fabrica("secreto", "HUASTECA-748291-0002-C"));
+    }
+}
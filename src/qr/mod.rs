@@ -0,0 +1,40 @@
+// src/qr/mod.rs
+// Códigos QR de respaldo (por si el celular no tiene NFC).
+use qrcode::{render::svg, EcLevel, QrCode};
+
+// Nivel M: aguanta ~15% de daño (rayones, stickers mal cortados) sin crecer tanto
+const NIVEL: EcLevel = EcLevel::M;
+
+fn codigo(texto: &str) -> Result<QrCode, qrcode::types::QrError> {
+    QrCode::with_error_correction_level(texto.as_bytes(), NIVEL)
+}
+
+/// QR en SVG (escala infinita, ideal para imprenta)
+pub fn svg(texto: &str, tamano_px: u32) -> Result<String, qrcode::types::QrError> {
+    Ok(codigo(texto)?
+        .render::<svg::Color>()
+        .min_dimensions(tamano_px, tamano_px)
+        .build())
+}
+
+/// QR en PNG
+pub fn png(texto: &str, tamano_px: u32) -> Result<Vec<u8>, String> {
+    let imagen = codigo(texto)
+        .map_err(|e| e.to_string())?
+        .render::<image::Luma<u8>>()
+        .min_dimensions(tamano_px, tamano_px)
+        .build();
+
+    let mut salida = std::io::Cursor::new(Vec::new());
+    imagen
+        .write_to(&mut salida, image::ImageFormat::Png)
+        .map_err(|e| e.to_string())?;
+    Ok(salida.into_inner())
+}
+
+/// Matriz de módulos (true = oscuro) con su ancho, para dibujar el QR a mano (PDF)
+pub fn modulos(texto: &str) -> Result<(usize, Vec<bool>), qrcode::types::QrError> {
+    let qr = codigo(texto)?;
+    let oscuros = qr.to_colors().into_iter().map(|c| c == qrcode::Color::Dark).collect();
+    Ok((qr.width(), oscuros))
+}
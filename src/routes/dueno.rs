@@ -0,0 +1,258 @@
+// src/routes/dueno.rs
+// Panel del dueño: con el PIN de su imán puede pausarlo, reactivarlo y
+// programar destinos por fecha, sin perder el destino que configuró.
+// No hay sesión: cada formulario del panel vuelve a mandar el PIN (campo oculto).
+use axum::{
+    extract::{Form, Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+};
+use mongodb::bson::{doc, oid::ObjectId, DateTime};
+use serde::Deserialize;
+use tokio::time::{sleep, Duration};
+
+use super::imanes::{codigo_valido, programacion_view, ProgramacionForm};
+use crate::{destinos, estados, models::{EstadoIman, Iman}, pin, seguridad, AppState};
+
+// Un PIN de 6 dígitos son solo un millón de opciones: pocos intentos por imán y luego a esperar
+const MAX_FALLOS_PIN: u32 = 5;
+const BLOQUEO_PIN_MS: i64 = 15 * 60 * 1000;
+
+#[derive(Deserialize)]
+pub struct DuenoForm {
+    pin: String,
+    // "ver", "pausar" o "reanudar"
+    accion: String,
+}
+
+#[derive(Deserialize)]
+pub struct PinForm {
+    pin: String,
+}
+
+fn render_dueno(state: &AppState, context: tera::Context, status: StatusCode) -> Response {
+    match state.tera.render("dueno.html", &context) {
+        Ok(html) => (status, Html(html)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Template error: {}", e)).into_response(),
+    }
+}
+
+// El panel completo, ya con el PIN verificado
+fn render_panel(state: &AppState, iman: &Iman, pin: &str, mut context: tera::Context, status: StatusCode) -> Response {
+    context.insert("codigo", &iman.codigo);
+    context.insert("pin", pin);
+    context.insert("estado", estados::clave(iman.estado));
+    context.insert("etiqueta", estados::etiqueta(iman.estado));
+    context.insert("target_url", &iman.target_url);
+    context.insert("programacion", &programacion_view(iman));
+    render_dueno(state, context, status)
+}
+
+fn error_pin(state: &AppState, codigo: &str, mensaje: &str, status: StatusCode) -> Response {
+    let mut context = tera::Context::new();
+    context.insert("codigo", codigo);
+    context.insert("error", mensaje);
+    render_dueno(state, context, status)
+}
+
+// Regresa el imán si el PIN es correcto; si no, la respuesta de error ya armada
+async fn verificar_pin(state: &AppState, codigo: &str, pin: &str) -> Result<Iman, Response> {
+    if !codigo_valido(codigo) {
+        return Err((StatusCode::NOT_FOUND, "❌ Imán no encontrado").into_response());
+    }
+
+    let collection = state.db.collection::<Iman>("imanes");
+    let iman = match collection.find_one(doc! { "codigo": codigo }, None).await {
+        Ok(Some(iman)) if iman.owner_pin_hash.is_some() || iman.owner_pin.is_some() => iman,
+        // Mismo mensaje para "no existe" y "PIN incorrecto": no revelamos cuál de los dos fue
+        Ok(_) => {
+            sleep(Duration::from_secs(2)).await;
+            return Err(error_pin(state, codigo, "Código o PIN incorrecto.", StatusCode::UNAUTHORIZED));
+        }
+        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response()),
+    };
+
+    // 🛡️ DEFENSA CONTRA FUERZA BRUTA: el conteo vive en Mongo, por imán, así que
+    // mandar muchas peticiones en paralelo (o a varias réplicas) no da más intentos
+    let ahora = DateTime::now();
+    let bloqueo_vencido = iman
+        .pin_fallo_at
+        .is_none_or(|at| ahora.timestamp_millis() - at.timestamp_millis() >= BLOQUEO_PIN_MS);
+    if iman.pin_fallos >= MAX_FALLOS_PIN && !bloqueo_vencido {
+        return Err(error_pin(state, codigo, "Demasiados intentos. Espera 15 minutos.", StatusCode::TOO_MANY_REQUESTS));
+    }
+
+    // Apartamos el intento ANTES de revisar el PIN, con compare-and-set sobre el contador:
+    // de varias peticiones que vieron el mismo valor solo una pasa
+    let fallos_vistos = if iman.pin_fallos == 0 { doc! { "$in": [0, null] } } else { doc! { "$eq": iman.pin_fallos } };
+    let siguiente = if iman.pin_fallos >= MAX_FALLOS_PIN { 1 } else { iman.pin_fallos + 1 };
+    let apartar = collection
+        .update_one(
+            doc! { "codigo": codigo, "pin_fallos": fallos_vistos },
+            doc! { "$set": { "pin_fallos": siguiente, "pin_fallo_at": ahora } },
+            None,
+        )
+        .await;
+    match apartar {
+        Ok(r) if r.modified_count == 1 => {}
+        Ok(_) => {
+            return Err(error_pin(state, codigo, "Hay otro intento en curso. Espera un momento.", StatusCode::TOO_MANY_REQUESTS));
+        }
+        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response()),
+    }
+
+    let pin = pin.trim().to_string();
+    let correcto = match (&iman.owner_pin_hash, &iman.owner_pin) {
+        (Some(guardado), _) => {
+            // PBKDF2 es CPU pura: fuera del runtime async
+            let guardado = guardado.clone();
+            let pin = pin.clone();
+            tokio::task::spawn_blocking(move || pin::coincide(&pin, &guardado)).await.unwrap_or(false)
+        }
+        (None, Some(plano)) => plano == &pin,
+        (None, None) => false,
+    };
+
+    if !correcto {
+        sleep(Duration::from_secs(2)).await;
+        return Err(error_pin(state, codigo, "Código o PIN incorrecto.", StatusCode::UNAUTHORIZED));
+    }
+
+    // Correcto: contador en cero y, si era un PIN viejo en texto plano, lo pasamos a hash
+    let mut update = doc! { "$set": { "pin_fallos": 0 } };
+    if iman.owner_pin_hash.is_none() {
+        let hash = tokio::task::spawn_blocking(move || pin::hash(&pin)).await;
+        if let Ok(hash) = hash {
+            update = doc! { "$set": { "pin_fallos": 0, "owner_pin_hash": hash }, "$unset": { "owner_pin": "" } };
+        }
+    }
+    if let Err(e) = collection.update_one(doc! { "codigo": codigo }, update, None).await {
+        eprintln!("❌ No se pudo reiniciar el contador de PIN de {}: {}", codigo, e);
+    }
+
+    Ok(iman)
+}
+
+// GET /v/:codigo/dueno
+pub async fn dueno_page(
+    State(state): State<AppState>,
+    Path(codigo): Path<String>,
+) -> Response {
+    let mut context = tera::Context::new();
+    context.insert("codigo", &codigo);
+    render_dueno(&state, context, StatusCode::OK)
+}
+
+// POST /api/dueno/:codigo
+pub async fn dueno_accion(
+    State(state): State<AppState>,
+    Path(codigo): Path<String>,
+    Form(form): Form<DuenoForm>,
+) -> Response {
+    let iman = match verificar_pin(&state, &codigo, &form.pin).await {
+        Ok(i) => i,
+        Err(response) => return response,
+    };
+
+    // El dueño solo alterna entre activo y pausado; lo que bloqueó un admin no lo puede reactivar
+    let (destino, desde) = match form.accion.as_str() {
+        "ver" => return render_panel(&state, &iman, &form.pin, tera::Context::new(), StatusCode::OK),
+        "pausar" => (EstadoIman::Paused, [EstadoIman::Claimed]),
+        "reanudar" if iman.alerta_seguridad.is_some() => {
+            let mut context = tera::Context::new();
+            context.insert("error", "Pausamos tu imán porque su destino salió en nuestra revisión de seguridad. Contáctanos para revisarlo.");
+            return render_panel(&state, &iman, &form.pin, context, StatusCode::CONFLICT);
+        }
+        "reanudar" => (EstadoIman::Claimed, [EstadoIman::Paused]),
+        _ => return (StatusCode::BAD_REQUEST, "❌ Acción no válida").into_response(),
+    };
+
+    let mut context = tera::Context::new();
+    let resultado = estados::transicionar(&state.db, &codigo, destino, Some(&desde), Some("dueño"), doc! {}).await;
+    match resultado {
+        Ok(iman) => {
+            context.insert("mensaje", if iman.estado == EstadoIman::Paused {
+                "⏸️ Tu imán está pausado. Quien lo escanee verá un aviso de \"no disponible\"."
+            } else {
+                "▶️ Tu imán está activo otra vez."
+            });
+            render_panel(&state, &iman, &form.pin, context, StatusCode::OK)
+        }
+        Err(estados::ErrorTransicion::NoPermitida { desde: EstadoIman::Disabled, .. }) => {
+            context.insert("error", "Este imán fue bloqueado por soporte. Contáctanos para revisarlo.");
+            render_panel(&state, &iman, &form.pin, context, StatusCode::CONFLICT)
+        }
+        Err(estados::ErrorTransicion::NoPermitida { desde, .. }) => {
+            context.insert("error", &format!("No se puede: tu imán está \"{}\".", estados::etiqueta(desde)));
+            render_panel(&state, &iman, &form.pin, context, StatusCode::CONFLICT)
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+// POST /api/dueno/:codigo/programacion
+pub async fn dueno_agregar_programacion(
+    State(state): State<AppState>,
+    Path(codigo): Path<String>,
+    Form(form): Form<ProgramacionForm>,
+) -> Response {
+    let pin = form.pin.clone().unwrap_or_default();
+    let iman = match verificar_pin(&state, &codigo, &pin).await {
+        Ok(i) => i,
+        Err(response) => return response,
+    };
+
+    // Mismas reglas que al configurar el imán por primera vez
+    let mut programado = destinos::validar_destino(form.url.trim())
+        .and_then(|_| destinos::nuevo_programado(&form.url, &form.inicio, &form.fin, form.tz_offset));
+    if let Ok(p) = &programado
+        && let seguridad::Veredicto::Bloqueado(motivo) = state.seguridad.revisar(&p.url).await
+    {
+        eprintln!("🚩 Programación de {} rechazada: {} ({})", codigo, p.url, motivo);
+        programado = Err("❌ Ese enlace está marcado como inseguro. Revisa que sea el sitio correcto.");
+    }
+
+    let mut context = tera::Context::new();
+    let programado = match programado {
+        Ok(p) => p,
+        Err(msg) => {
+            context.insert("error", msg);
+            return render_panel(&state, &iman, &pin, context, StatusCode::UNPROCESSABLE_ENTITY);
+        }
+    };
+
+    let Ok(doc_programado) = mongodb::bson::to_document(&programado) else {
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Error serializando la programación").into_response();
+    };
+    let update = doc! { "$push": { "programacion": doc_programado } };
+    if let Err(e) = state.db.collection::<Iman>("imanes").update_one(doc! { "codigo": &codigo }, update, None).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response();
+    }
+
+    let mut iman = iman;
+    iman.programacion.push(programado);
+    context.insert("mensaje", "📅 Destino programado.");
+    render_panel(&state, &iman, &pin, context, StatusCode::OK)
+}
+
+// POST /api/dueno/:codigo/programacion/:id/borrar
+pub async fn dueno_borrar_programacion(
+    State(state): State<AppState>,
+    Path((codigo, id)): Path<(String, String)>,
+    Form(form): Form<PinForm>,
+) -> Response {
+    let mut iman = match verificar_pin(&state, &codigo, &form.pin).await {
+        Ok(i) => i,
+        Err(response) => return response,
+    };
+
+    if let Ok(oid) = ObjectId::parse_str(&id) {
+        let update = doc! { "$pull": { "programacion": { "id": oid } } };
+        if let Err(e) = state.db.collection::<Iman>("imanes").update_one(doc! { "codigo": &codigo }, update, None).await {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response();
+        }
+        iman.programacion.retain(|p| p.id != oid);
+    }
+
+    render_panel(&state, &iman, &form.pin, tera::Context::new(), StatusCode::OK)
+}
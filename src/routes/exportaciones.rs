@@ -0,0 +1,232 @@
+// src/routes/exportaciones.rs
+// "Exportar nuevos" con historial: cada archivo queda registrado como una
+// exportación con los códigos exactos que salieron.
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use futures::stream::TryStreamExt;
+use mongodb::bson::{doc, oid::ObjectId, DateTime, Document};
+use tower_cookies::Cookies;
+
+use super::{export_response, formato_export, ExportQuery};
+use crate::{estados, models::{EstadoIman, Exportacion, Iman}, AppState};
+
+// GET /api/admin/export_csv
+//
+// Sin transacciones multi-documento (no siempre tenemos replica set):
+// 1. Registramos la exportación vacía.
+// 2. Reclamamos todos los recién generados con un solo update_many que también
+//    les pone export_id. Si otro export se los ganó, simplemente no cambian.
+// 3. El archivo sale de lo que quedó con nuestro export_id, ni uno más ni uno menos.
+//    Lo mismo las re-descargas: si algo truena a medias, el export_id ya está en los imanes.
+pub async fn export_csv(
+    cookies: Cookies,
+    State(state): State<AppState>,
+    axum::extract::Query(query): axum::extract::Query<ExportQuery>,
+) -> Response {
+    if cookies.get("admin_session").is_none() {
+        return Redirect::to("/login").into_response();
+    }
+
+    // Un formato inválido no debe gastarse el lote: se revisa antes de marcar nada
+    if let Err(error) = formato_export(&query) {
+        return error.into_response();
+    }
+
+    let usuario = cookies
+        .get("admin_operador")
+        .map(|c| c.value().to_string())
+        .unwrap_or_else(|| "admin".to_string());
+
+    let collection = state.db.collection::<Iman>("imanes");
+    let exportaciones = state.db.collection::<Exportacion>("exportaciones");
+
+    // 1. Registro de la exportación (primero, para que nada quede marcado sin dueño)
+    let export_id = ObjectId::new();
+    let registro = Exportacion {
+        id: export_id,
+        created_at: DateTime::now(),
+        usuario,
+        total: 0,
+        revertida: false,
+        revertida_at: None,
+    };
+    if let Err(e) = exportaciones.insert_one(registro, None).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response();
+    }
+
+    // 2. RECLAMO ATÓMICO: Solo se marcan los que sigan en "generated" (sin cargarlos:
+    // con lotes grandes una lista de _ids no cabría en el comando)
+    let claim = estados::filtro_origen(EstadoIman::Exported);
+    let update = estados::update(EstadoIman::Exported, None, doc! { "exported": true, "export_id": export_id });
+    if let Err(e) = collection.update_many(claim, update, None).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response();
+    }
+
+    // 3. Lo que quedó con nuestro export_id es exactamente lo que va en el archivo
+    let total = match contar_marcados(&state, export_id).await {
+        Ok(n) => n,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response(),
+    };
+
+    if total == 0 {
+        // No había recién generados (u otro export nos los ganó todos)
+        exportaciones.delete_one(doc! { "_id": export_id }, None).await.ok();
+        return (StatusCode::OK, "⚠️ No hay imanes nuevos para exportar. Genera un lote primero.").into_response();
+    }
+
+    if let Err(e) = exportaciones
+        .update_one(doc! { "_id": export_id }, doc! { "$set": { "total": total } }, None)
+        .await
+    {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response();
+    }
+
+    // 4. Devolver CSV (si la descarga falla, se puede repetir desde el historial)
+    stream_exportacion(&state, export_id, &query).await
+}
+
+async fn contar_marcados(state: &AppState, export_id: ObjectId) -> Result<u32, mongodb::error::Error> {
+    let total = state
+        .db
+        .collection::<Document>("imanes")
+        .count_documents(doc! { "export_id": export_id }, None)
+        .await?;
+    Ok(total as u32)
+}
+
+// Manda el archivo de una exportación registrada (nueva o re-descarga)
+async fn stream_exportacion(state: &AppState, export_id: ObjectId, query: &ExportQuery) -> Response {
+    let registro = match state
+        .db
+        .collection::<Exportacion>("exportaciones")
+        .find_one(doc! { "_id": export_id }, None)
+        .await
+    {
+        Ok(Some(r)) => r,
+        Ok(None) => return (StatusCode::NOT_FOUND, "❌ Exportación no encontrada").into_response(),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response(),
+    };
+
+    let options = mongodb::options::FindOptions::builder()
+        .sort(doc! { "codigo": 1 })
+        .build();
+
+    let cursor = match state
+        .db
+        .collection::<Iman>("imanes")
+        .find(doc! { "export_id": export_id }, options)
+        .await
+    {
+        Ok(c) => c,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response(),
+    };
+
+    let fecha = chrono::DateTime::from_timestamp_millis(registro.created_at.timestamp_millis())
+        .map(|dt| dt.format("%Y%m%d_%H%M").to_string())
+        .unwrap_or_default();
+    export_response(cursor, query, &format!("lote_produccion_{}", fecha)).await
+}
+
+// GET /admin/exportaciones
+pub async fn exportaciones_page(
+    cookies: Cookies,
+    State(state): State<AppState>,
+) -> Response {
+    if cookies.get("admin_session").is_none() {
+        return Redirect::to("/login").into_response();
+    }
+
+    let options = mongodb::options::FindOptions::builder()
+        .sort(doc! { "created_at": -1 })
+        .limit(100)
+        .build();
+    let cursor_historial = state
+        .db
+        .collection::<Exportacion>("exportaciones")
+        .find(doc! {}, options)
+        .await;
+
+    let mut historial = Vec::new();
+    if let Ok(mut cursor) = cursor_historial {
+        while let Ok(Some(mut registro)) = cursor.try_next().await {
+            // Se cayó antes de guardar el total: lo sacamos de los imanes
+            if registro.total == 0
+                && let Ok(total) = contar_marcados(&state, registro.id).await
+            {
+                registro.total = total;
+            }
+            historial.push(serde_json::json!({
+                "id": registro.id.to_hex(),
+                "fecha": chrono::DateTime::from_timestamp_millis(registro.created_at.timestamp_millis()).map(|dt| dt.to_rfc3339()),
+                "usuario": registro.usuario,
+                "total": registro.total,
+                "revertida": registro.revertida,
+            }));
+        }
+    }
+
+    let mut context = tera::Context::new();
+    context.insert("exportaciones", &historial);
+
+    match state.tera.render("exportaciones.html", &context) {
+        Ok(html) => Html(html).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+    }
+}
+
+// GET /admin/exportaciones/:id/csv
+pub async fn redescargar_exportacion(
+    cookies: Cookies,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    axum::extract::Query(query): axum::extract::Query<ExportQuery>,
+) -> Response {
+    if cookies.get("admin_session").is_none() {
+        return Redirect::to("/login").into_response();
+    }
+
+    let Ok(oid) = ObjectId::parse_str(&id) else {
+        return (StatusCode::NOT_FOUND, "❌ Exportación no encontrada").into_response();
+    };
+
+    stream_exportacion(&state, oid, &query).await
+}
+
+// POST /api/admin/exportaciones/:id/desmarcar
+// Regresa los imanes a "sin exportar" para que salgan en el siguiente archivo.
+pub async fn desmarcar_exportacion(
+    cookies: Cookies,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Response {
+    if cookies.get("admin_session").is_none() {
+        return Redirect::to("/login").into_response();
+    }
+
+    let Ok(oid) = ObjectId::parse_str(&id) else {
+        return (StatusCode::NOT_FOUND, "❌ Exportación no encontrada").into_response();
+    };
+
+    // Solo tocamos los que siguen ligados a ESTA exportación y sin avanzar
+    // (si ya se grabaron, vendieron o salieron en otra, se respetan)
+    let mut filter = estados::filtro_origen(EstadoIman::Generated);
+    filter.insert("export_id", oid);
+    // El export_id se queda: la re-descarga de esta exportación lo usa (el siguiente export lo reemplaza)
+    let update = estados::update(EstadoIman::Generated, Some("exportación desmarcada"), doc! { "exported": false });
+    if let Err(e) = state.db.collection::<Iman>("imanes").update_many(filter, update, None).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response();
+    }
+
+    let update = doc! { "$set": { "revertida": true, "revertida_at": DateTime::now() } };
+    state
+        .db
+        .collection::<Exportacion>("exportaciones")
+        .update_one(doc! { "_id": oid }, update, None)
+        .await
+        .ok();
+
+    Redirect::to("/admin/exportaciones").into_response()
+}
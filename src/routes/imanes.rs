@@ -0,0 +1,468 @@
+// src/routes/imanes.rs
+// Ficha de un imán en el panel: estado, historial y destinos programados.
+use axum::{
+    extract::{Form, Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use mongodb::bson::{doc, oid::ObjectId, DateTime};
+use serde::Deserialize;
+use tower_cookies::Cookies;
+
+use crate::{codes, destinos, estados, models::Iman, seguridad, AppState};
+
+#[derive(Deserialize)]
+pub struct ProgramacionForm {
+    pub(super) url: String,
+    #[serde(default)]
+    pub(super) inicio: String,
+    #[serde(default)]
+    pub(super) fin: String,
+    // Minutos de Date.getTimezoneOffset() del navegador (las fechas llegan en su hora local)
+    #[serde(default)]
+    pub(super) tz_offset: i64,
+    // Solo lo manda el panel del dueño
+    pub(super) pin: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ReglaForm {
+    // "", "ios" o "android"
+    #[serde(default)]
+    so: String,
+    #[serde(default)]
+    idioma: String,
+    url: String,
+    #[serde(default)]
+    deep_link: String,
+}
+
+#[derive(Deserialize)]
+pub struct VarianteForm {
+    nombre: String,
+    url: String,
+    // Llega como texto para responder con un error amigable si no es número
+    peso: String,
+}
+
+#[derive(Deserialize)]
+pub struct UtmForm {
+    #[serde(default)]
+    utm: String,
+}
+
+fn fecha_iso(fecha: Option<DateTime>) -> Option<String> {
+    fecha
+        .and_then(|dt| chrono::DateTime::from_timestamp_millis(dt.timestamp_millis()))
+        .map(|dt| dt.to_rfc3339())
+}
+
+/// Destinos programados listos para Tera (con cuál está vigente ahora mismo)
+pub(super) fn programacion_view(iman: &Iman) -> Vec<serde_json::Value> {
+    let ahora = DateTime::now();
+    let vigente = destinos::programado_vigente(iman, ahora).map(|p| p.id);
+
+    iman.programacion
+        .iter()
+        .map(|p| {
+            serde_json::json!({
+                "id": p.id.to_hex(),
+                "url": p.url,
+                "inicio": fecha_iso(p.inicio),
+                "fin": fecha_iso(p.fin),
+                "vigente": vigente == Some(p.id),
+                "terminado": p.fin.is_some_and(|f| f <= ahora),
+            })
+        })
+        .collect()
+}
+
+/// Variantes A/B con su reparto esperado (por peso) y el real (por escaneos)
+pub(super) fn variantes_view(iman: &Iman) -> Vec<serde_json::Value> {
+    let peso_total: u32 = iman.variantes.iter().map(|v| v.peso).sum();
+    let visitas_total: i64 = iman.variantes.iter().map(|v| v.visitas).sum();
+    let porcentaje = |parte: f64, total: f64| if total > 0.0 { (parte * 100.0 / total).round() } else { 0.0 };
+
+    iman.variantes
+        .iter()
+        .map(|v| serde_json::json!({
+            "id": v.id.to_hex(),
+            "nombre": v.nombre,
+            "url": v.url,
+            "peso": v.peso,
+            "visitas": v.visitas,
+            "esperado": porcentaje(v.peso as f64, peso_total as f64),
+            "real": porcentaje(v.visitas as f64, visitas_total as f64),
+        }))
+        .collect()
+}
+
+// Los códigos van en redirecciones y rutas: si no tienen forma de código, ni los buscamos
+pub(super) fn codigo_valido(codigo: &str) -> bool {
+    !matches!(codes::clasificar(codigo), codes::Formato::Invalido)
+}
+
+async fn buscar(state: &AppState, codigo: &str) -> Result<Option<Iman>, mongodb::error::Error> {
+    state
+        .db
+        .collection::<Iman>("imanes")
+        .find_one(doc! { "codigo": codigo }, None)
+        .await
+}
+
+// Ficha del imán; `extra` trae avisos (errores de formulario, etc.)
+async fn render_iman(state: &AppState, codigo: &str, extra: tera::Context) -> Response {
+    if !codigo_valido(codigo) {
+        return (StatusCode::NOT_FOUND, "❌ Imán no encontrado").into_response();
+    }
+
+    let iman = match buscar(state, codigo).await {
+        Ok(Some(i)) => i,
+        Ok(None) => return (StatusCode::NOT_FOUND, "❌ Imán no encontrado").into_response(),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response(),
+    };
+
+    let historial: Vec<serde_json::Value> = iman
+        .historial
+        .iter()
+        .rev()
+        .map(|c| serde_json::json!({
+            "etiqueta": estados::etiqueta(c.estado),
+            "at": fecha_iso(Some(c.at)),
+            "motivo": c.motivo,
+        }))
+        .collect();
+
+    let mut context = tera::Context::new();
+    context.insert("iman", &serde_json::json!({
+        "codigo": iman.codigo,
+        "estado": estados::clave(iman.estado),
+        "etiqueta": estados::etiqueta(iman.estado),
+        "target_url": iman.target_url,
+        "url_vigente": destinos::url_vigente(&iman, DateTime::now()),
+        "visitas": iman.visitas,
+        "modo": iman.modo,
+        "utm": iman.utm,
+        "alerta": iman.alerta_seguridad.as_ref().map(|a| serde_json::json!({
+            "url": a.url,
+            "motivo": a.motivo,
+            "at": fecha_iso(Some(a.at)),
+        })),
+        "lote_nombre": iman.lote_nombre,
+        "lote_ts": iman.lote_fecha.map(|f| f.timestamp_millis()),
+        "activated_at": fecha_iso(iman.activated_at),
+        "last_scan_at": fecha_iso(iman.last_scan_at),
+    }));
+    context.insert("historial", &historial);
+    context.insert("programacion", &programacion_view(&iman));
+    context.insert("reglas", &iman.reglas.iter().map(|r| serde_json::json!({
+        "id": r.id.to_hex(),
+        "so": r.so,
+        "idioma": r.idioma,
+        "url": r.url,
+        "deep_link": r.deep_link,
+    })).collect::<Vec<_>>());
+    context.insert("variantes", &variantes_view(&iman));
+    context.insert("landing", &iman.landing);
+    context.insert("contacto", &iman.contacto);
+    // De la red WiFi solo enseñamos el nombre: la contraseña es del cliente
+    context.insert("wifi", &iman.wifi.as_ref().map(|w| serde_json::json!({
+        "ssid": w.ssid,
+        "seguridad": w.seguridad,
+        "oculta": w.oculta,
+        "con_password": !w.password.is_empty(),
+    })));
+    context.extend(extra);
+
+    match state.tera.render("iman.html", &context) {
+        Ok(html) => Html(html).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+    }
+}
+
+// GET /admin/imanes/:codigo
+pub async fn iman_page(
+    cookies: Cookies,
+    State(state): State<AppState>,
+    Path(codigo): Path<String>,
+) -> Response {
+    if cookies.get("admin_session").is_none() {
+        return Redirect::to("/login").into_response();
+    }
+
+    render_iman(&state, codigo.trim(), tera::Context::new()).await
+}
+
+// POST /api/admin/imanes/:codigo/programacion
+pub async fn agregar_programacion(
+    cookies: Cookies,
+    State(state): State<AppState>,
+    Path(codigo): Path<String>,
+    Form(form): Form<ProgramacionForm>,
+) -> Response {
+    if cookies.get("admin_session").is_none() {
+        return Redirect::to("/login").into_response();
+    }
+    if !codigo_valido(&codigo) {
+        return (StatusCode::NOT_FOUND, "❌ Imán no encontrado").into_response();
+    }
+
+    // El admin puede programar cualquier sitio https (no solo redes sociales), pero pasa la lista negra
+    let mut programado = if !form.url.trim().starts_with("https://") || url::Url::parse(form.url.trim()).is_err() {
+        Err("❌ El destino debe ser un enlace https:// válido")
+    } else {
+        destinos::nuevo_programado(&form.url, &form.inicio, &form.fin, form.tz_offset)
+    };
+    if let Ok(p) = &programado
+        && let seguridad::Veredicto::Bloqueado(motivo) = state.seguridad.revisar(&p.url).await
+    {
+        eprintln!("🚩 Programación de {} rechazada: {} ({})", codigo, p.url, motivo);
+        programado = Err("❌ Ese enlace está marcado como inseguro. Revisa que sea el sitio correcto.");
+    }
+
+    let programado = match programado {
+        Ok(p) => p,
+        Err(msg) => {
+            let mut extra = tera::Context::new();
+            extra.insert("error_programacion", msg);
+            let mut response = render_iman(&state, &codigo, extra).await;
+            if response.status() == StatusCode::OK {
+                *response.status_mut() = StatusCode::UNPROCESSABLE_ENTITY;
+            }
+            return response;
+        }
+    };
+
+    let Ok(programado) = mongodb::bson::to_document(&programado) else {
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Error serializando la programación").into_response();
+    };
+    let update = doc! { "$push": { "programacion": programado } };
+    if let Err(e) = state.db.collection::<Iman>("imanes").update_one(doc! { "codigo": &codigo }, update, None).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response();
+    }
+
+    Redirect::to(&format!("/admin/imanes/{}", codigo)).into_response()
+}
+
+// POST /api/admin/imanes/:codigo/programacion/:id/borrar
+pub async fn borrar_programacion(
+    cookies: Cookies,
+    State(state): State<AppState>,
+    Path((codigo, id)): Path<(String, String)>,
+) -> Response {
+    if cookies.get("admin_session").is_none() {
+        return Redirect::to("/login").into_response();
+    }
+    if !codigo_valido(&codigo) {
+        return (StatusCode::NOT_FOUND, "❌ Imán no encontrado").into_response();
+    }
+
+    if let Ok(oid) = ObjectId::parse_str(&id) {
+        let update = doc! { "$pull": { "programacion": { "id": oid } } };
+        if let Err(e) = state.db.collection::<Iman>("imanes").update_one(doc! { "codigo": &codigo }, update, None).await {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response();
+        }
+    }
+
+    Redirect::to(&format!("/admin/imanes/{}", codigo)).into_response()
+}
+
+// POST /api/admin/imanes/:codigo/reglas
+pub async fn agregar_regla(
+    cookies: Cookies,
+    State(state): State<AppState>,
+    Path(codigo): Path<String>,
+    Form(form): Form<ReglaForm>,
+) -> Response {
+    if cookies.get("admin_session").is_none() {
+        return Redirect::to("/login").into_response();
+    }
+    if !codigo_valido(&codigo) {
+        return (StatusCode::NOT_FOUND, "❌ Imán no encontrado").into_response();
+    }
+
+    let mut regla = destinos::nueva_regla(&form.so, &form.idioma, &form.url, &form.deep_link);
+    if let Ok(r) = &regla
+        && let seguridad::Veredicto::Bloqueado(motivo) = state.seguridad.revisar(&r.url).await
+    {
+        eprintln!("🚩 Regla de {} rechazada: {} ({})", codigo, r.url, motivo);
+        regla = Err("❌ Ese enlace está marcado como inseguro. Revisa que sea el sitio correcto.");
+    }
+
+    let regla = match regla {
+        Ok(r) => r,
+        Err(msg) => {
+            let mut extra = tera::Context::new();
+            extra.insert("error_regla", msg);
+            let mut response = render_iman(&state, &codigo, extra).await;
+            if response.status() == StatusCode::OK {
+                *response.status_mut() = StatusCode::UNPROCESSABLE_ENTITY;
+            }
+            return response;
+        }
+    };
+
+    let Ok(regla) = mongodb::bson::to_document(&regla) else {
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Error serializando la regla").into_response();
+    };
+    let update = doc! { "$push": { "reglas": regla } };
+    if let Err(e) = state.db.collection::<Iman>("imanes").update_one(doc! { "codigo": &codigo }, update, None).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response();
+    }
+
+    Redirect::to(&format!("/admin/imanes/{}", codigo)).into_response()
+}
+
+// POST /api/admin/imanes/:codigo/reglas/:id/borrar
+pub async fn borrar_regla(
+    cookies: Cookies,
+    State(state): State<AppState>,
+    Path((codigo, id)): Path<(String, String)>,
+) -> Response {
+    if cookies.get("admin_session").is_none() {
+        return Redirect::to("/login").into_response();
+    }
+    if !codigo_valido(&codigo) {
+        return (StatusCode::NOT_FOUND, "❌ Imán no encontrado").into_response();
+    }
+
+    if let Ok(oid) = ObjectId::parse_str(&id) {
+        let update = doc! { "$pull": { "reglas": { "id": oid } } };
+        if let Err(e) = state.db.collection::<Iman>("imanes").update_one(doc! { "codigo": &codigo }, update, None).await {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response();
+        }
+    }
+
+    Redirect::to(&format!("/admin/imanes/{}", codigo)).into_response()
+}
+
+// POST /api/admin/imanes/:codigo/variantes
+pub async fn agregar_variante(
+    cookies: Cookies,
+    State(state): State<AppState>,
+    Path(codigo): Path<String>,
+    Form(form): Form<VarianteForm>,
+) -> Response {
+    if cookies.get("admin_session").is_none() {
+        return Redirect::to("/login").into_response();
+    }
+    if !codigo_valido(&codigo) {
+        return (StatusCode::NOT_FOUND, "❌ Imán no encontrado").into_response();
+    }
+
+    let mut variante = form
+        .peso
+        .trim()
+        .parse::<u32>()
+        .map_err(|_| "⚠️ El peso debe ser un número entero.")
+        .and_then(|peso| destinos::nueva_variante(&form.nombre, &form.url, peso));
+    if let Ok(v) = &variante
+        && let seguridad::Veredicto::Bloqueado(motivo) = state.seguridad.revisar(&v.url).await
+    {
+        eprintln!("🚩 Variante de {} rechazada: {} ({})", codigo, v.url, motivo);
+        variante = Err("❌ Ese enlace está marcado como inseguro. Revisa que sea el sitio correcto.");
+    }
+
+    let variante = match variante {
+        Ok(v) => v,
+        Err(msg) => {
+            let mut extra = tera::Context::new();
+            extra.insert("error_variante", msg);
+            let mut response = render_iman(&state, &codigo, extra).await;
+            if response.status() == StatusCode::OK {
+                *response.status_mut() = StatusCode::UNPROCESSABLE_ENTITY;
+            }
+            return response;
+        }
+    };
+
+    let Ok(variante) = mongodb::bson::to_document(&variante) else {
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Error serializando la variante").into_response();
+    };
+    let update = doc! { "$push": { "variantes": variante } };
+    if let Err(e) = state.db.collection::<Iman>("imanes").update_one(doc! { "codigo": &codigo }, update, None).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response();
+    }
+
+    Redirect::to(&format!("/admin/imanes/{}", codigo)).into_response()
+}
+
+// POST /api/admin/imanes/:codigo/variantes/:id/borrar
+pub async fn borrar_variante(
+    cookies: Cookies,
+    State(state): State<AppState>,
+    Path((codigo, id)): Path<(String, String)>,
+) -> Response {
+    if cookies.get("admin_session").is_none() {
+        return Redirect::to("/login").into_response();
+    }
+    if !codigo_valido(&codigo) {
+        return (StatusCode::NOT_FOUND, "❌ Imán no encontrado").into_response();
+    }
+
+    if let Ok(oid) = ObjectId::parse_str(&id) {
+        let update = doc! { "$pull": { "variantes": { "id": oid } } };
+        if let Err(e) = state.db.collection::<Iman>("imanes").update_one(doc! { "codigo": &codigo }, update, None).await {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response();
+        }
+    }
+
+    Redirect::to(&format!("/admin/imanes/{}", codigo)).into_response()
+}
+
+// POST /api/admin/imanes/:codigo/utm (vacío = usar la del lote)
+pub async fn guardar_utm(
+    cookies: Cookies,
+    State(state): State<AppState>,
+    Path(codigo): Path<String>,
+    Form(form): Form<UtmForm>,
+) -> Response {
+    if cookies.get("admin_session").is_none() {
+        return Redirect::to("/login").into_response();
+    }
+    if !codigo_valido(&codigo) {
+        return (StatusCode::NOT_FOUND, "❌ Imán no encontrado").into_response();
+    }
+
+    let update = match destinos::validar_utm(&form.utm) {
+        Ok(Some(utm)) => doc! { "$set": { "utm": utm } },
+        Ok(None) => doc! { "$unset": { "utm": "" } },
+        Err(msg) => {
+            let mut extra = tera::Context::new();
+            extra.insert("error_utm", msg);
+            let mut response = render_iman(&state, &codigo, extra).await;
+            if response.status() == StatusCode::OK {
+                *response.status_mut() = StatusCode::UNPROCESSABLE_ENTITY;
+            }
+            return response;
+        }
+    };
+
+    if let Err(e) = state.db.collection::<Iman>("imanes").update_one(doc! { "codigo": &codigo }, update, None).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response();
+    }
+
+    Redirect::to(&format!("/admin/imanes/{}", codigo)).into_response()
+}
+
+// POST /api/admin/imanes/:codigo/alerta/borrar
+// Después de revisarlo a mano: quita la marca (el estado no cambia; reactivarlo es aparte)
+pub async fn quitar_alerta(
+    cookies: Cookies,
+    State(state): State<AppState>,
+    Path(codigo): Path<String>,
+) -> Response {
+    if cookies.get("admin_session").is_none() {
+        return Redirect::to("/login").into_response();
+    }
+    if !codigo_valido(&codigo) {
+        return (StatusCode::NOT_FOUND, "❌ Imán no encontrado").into_response();
+    }
+
+    let update = doc! { "$unset": { "alerta_seguridad": "" } };
+    if let Err(e) = state.db.collection::<Iman>("imanes").update_one(doc! { "codigo": &codigo }, update, None).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response();
+    }
+
+    Redirect::to(&format!("/admin/imanes/{}", codigo)).into_response()
+}
@@ -0,0 +1,356 @@
+// src/routes/importar.rs
+// Importar códigos de tags que ya se grabaron con el sistema anterior.
+// CSV con columnas: codigo (obligatoria), target_url, active, lote (opcionales).
+use std::collections::{HashMap, HashSet};
+
+use axum::{
+    extract::{Multipart, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use futures::stream::TryStreamExt;
+use mongodb::bson::{doc, DateTime, Document};
+use serde::Serialize;
+use tower_cookies::Cookies;
+
+use crate::{codes, destinos, estados, models::{CambioEstado, EstadoIman, Iman}, seguridad::Veredicto, AppState};
+
+// Tope de filas por archivo (arriba de esto mejor partir el archivo)
+const MAX_FILAS: usize = 20_000;
+
+// Una fila ya validada
+struct FilaImport {
+    fila: usize,
+    codigo: String,
+    target_url: Option<String>,
+    active: bool,
+    lote: Option<String>,
+}
+
+// Un problema o aviso para el reporte
+#[derive(Serialize)]
+struct Aviso {
+    fila: usize,
+    codigo: String,
+    mensaje: String,
+}
+
+#[derive(Serialize, Default)]
+struct Resumen {
+    filas: usize,
+    validas: usize,
+    nuevas: usize,
+    existentes: usize,
+    actualizadas: usize,
+    omitidas: usize,
+    errores: usize,
+}
+
+fn parse_bool(valor: &str) -> Option<bool> {
+    match valor.trim().to_lowercase().as_str() {
+        "" | "false" | "0" | "no" => Some(false),
+        "true" | "1" | "si" | "sí" => Some(true),
+        _ => None,
+    }
+}
+
+// Lee y valida el CSV completo. Regresa las filas buenas y los errores por fila.
+fn parse_csv(contenido: &str) -> Result<(Vec<FilaImport>, Vec<Aviso>), String> {
+    // Excel a veces deja la marca BOM al inicio
+    let contenido = contenido.trim_start_matches('\u{feff}');
+    let mut lector = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(contenido.as_bytes());
+
+    let encabezados = lector.headers().map_err(|e| format!("No se pudo leer el encabezado: {}", e))?.clone();
+    let columna = |nombre: &str| encabezados.iter().position(|h| h.eq_ignore_ascii_case(nombre));
+    let Some(col_codigo) = columna("codigo") else {
+        return Err("El CSV necesita una columna \"codigo\".".to_string());
+    };
+    let (col_url, col_active, col_lote) = (columna("target_url"), columna("active"), columna("lote"));
+
+    let mut filas = Vec::new();
+    let mut errores = Vec::new();
+    let mut vistos = HashSet::new();
+
+    for (i, registro) in lector.records().enumerate() {
+        // +2: la fila 1 es el encabezado y los humanos cuentan desde 1
+        let fila = i + 2;
+        if i >= MAX_FILAS {
+            return Err(format!("El archivo tiene más de {} filas. Pártelo en varios.", MAX_FILAS));
+        }
+
+        let registro = match registro {
+            Ok(r) => r,
+            Err(e) => {
+                errores.push(Aviso { fila, codigo: String::new(), mensaje: format!("Fila ilegible: {}", e) });
+                continue;
+            }
+        };
+        let campo = |col: Option<usize>| col.and_then(|c| registro.get(c)).unwrap_or("").to_string();
+
+        let codigo = campo(Some(col_codigo));
+        let mut error = |mensaje: &str| errores.push(Aviso { fila, codigo: codigo.clone(), mensaje: mensaje.to_string() });
+
+        match codes::clasificar(&codigo) {
+            codes::Formato::Invalido => {
+                error("Código vacío, muy largo, con caracteres raros o con verificador incorrecto");
+                continue;
+            }
+            codes::Formato::Legacy if !codes::permitir_legacy() => {
+                error("Código sin dígito verificador y PERMITIR_CODIGOS_LEGACY=false: daría 404 al escanear");
+                continue;
+            }
+            _ => {}
+        }
+
+        if !vistos.insert(codigo.clone()) {
+            error("Código repetido dentro del mismo archivo");
+            continue;
+        }
+
+        // Las mismas reglas que el setup: si no, el CSV sería la puerta trasera
+        let target_url = Some(campo(col_url)).filter(|u| !u.is_empty());
+        if let Some(url) = &target_url
+            && let Err(msg) = destinos::validar_destino(url)
+        {
+            error(msg);
+            continue;
+        }
+
+        let Some(active) = parse_bool(&campo(col_active)) else {
+            error("active debe ser true/false, 1/0 o si/no");
+            continue;
+        };
+        if active && target_url.is_none() {
+            error("Un imán activo necesita target_url");
+            continue;
+        }
+
+        let lote = Some(campo(col_lote)).filter(|l| !l.is_empty());
+        filas.push(FilaImport { fila, codigo, target_url, active, lote });
+    }
+
+    Ok((filas, errores))
+}
+
+// GET /admin/importar
+pub async fn importar_page(
+    cookies: Cookies,
+    State(state): State<AppState>,
+) -> Response {
+    if cookies.get("admin_session").is_none() {
+        return Redirect::to("/login").into_response();
+    }
+
+    render_importar(&state, tera::Context::new())
+}
+
+fn render_importar(state: &AppState, context: tera::Context) -> Response {
+    match state.tera.render("importar.html", &context) {
+        Ok(html) => Html(html).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+    }
+}
+
+// La página con el error arriba y 400 (para no confundirlo con un import que sí pasó)
+fn render_error(state: &AppState, mensaje: &str) -> Response {
+    let mut context = tera::Context::new();
+    context.insert("error", mensaje);
+    let mut response = render_importar(state, context);
+    if response.status() == StatusCode::OK {
+        *response.status_mut() = StatusCode::BAD_REQUEST;
+    }
+    response
+}
+
+// POST /api/admin/importar (multipart)
+// Campos: archivo (o contenido, cuando viene de la vista previa), dry_run, sobrescribir, lote
+pub async fn importar_csv(
+    cookies: Cookies,
+    State(state): State<AppState>,
+    mut multipart: Multipart,
+) -> Response {
+    if cookies.get("admin_session").is_none() {
+        return Redirect::to("/login").into_response();
+    }
+
+    let mut contenido = String::new();
+    let mut dry_run = false;
+    let mut sobrescribir = false;
+    let mut lote_default: Option<String> = None;
+
+    loop {
+        let campo = match multipart.next_field().await {
+            Ok(Some(campo)) => campo,
+            Ok(None) => break,
+            // Subida cortada o demasiado grande: no importamos medio archivo
+            Err(e) => return render_error(&state, &format!("No se pudo leer el formulario: {}", e)),
+        };
+        let nombre = campo.name().unwrap_or("").to_string();
+        let Ok(valor) = campo.text().await else {
+            return render_error(&state, "No se pudo leer el archivo (¿está en UTF-8?).");
+        };
+
+        match nombre.as_str() {
+            "archivo" | "contenido" if !valor.trim().is_empty() => contenido = valor,
+            "dry_run" => dry_run = true,
+            "sobrescribir" => sobrescribir = true,
+            "lote" => lote_default = Some(valor.trim().to_string()).filter(|l| !l.is_empty()),
+            _ => {}
+        }
+    }
+
+    let mut context = tera::Context::new();
+    context.insert("dry_run", &dry_run);
+    context.insert("sobrescribir", &sobrescribir);
+    context.insert("lote", &lote_default);
+
+    let (filas, mut errores) = match parse_csv(&contenido) {
+        Ok(v) => v,
+        Err(msg) => {
+            context.insert("error", &msg);
+            return render_importar(&state, context);
+        }
+    };
+
+    // Y la misma revisión de seguridad que el setup (lista negra, dominios parecidos...)
+    let mut revisadas = Vec::with_capacity(filas.len());
+    for fila in filas {
+        if let Some(url) = &fila.target_url
+            && let Veredicto::Bloqueado(motivo) = state.seguridad.revisar(url).await
+        {
+            errores.push(Aviso { fila: fila.fila, codigo: fila.codigo, mensaje: format!("target_url marcado como inseguro ({})", motivo) });
+            continue;
+        }
+        revisadas.push(fila);
+    }
+    let filas = revisadas;
+    errores.sort_by_key(|e| e.fila);
+
+    let collection = state.db.collection::<Iman>("imanes");
+
+    // CONFLICTOS: ¿Cuáles de estos códigos ya existen?
+    let codigos: Vec<&str> = filas.iter().map(|f| f.codigo.as_str()).collect();
+    let options = mongodb::options::FindOptions::builder()
+        .projection(doc! { "codigo": 1, "target_url": 1, "active": 1 })
+        .build();
+    let existentes: HashMap<String, Document> = match collection
+        .clone_with_type::<Document>()
+        .find(doc! { "codigo": { "$in": &codigos } }, options)
+        .await
+    {
+        Ok(cursor) => match cursor.try_collect::<Vec<Document>>().await {
+            Ok(docs) => docs
+                .into_iter()
+                .filter_map(|d| Some((d.get_str("codigo").ok()?.to_string(), d)))
+                .collect(),
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response(),
+        },
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response(),
+    };
+
+    let mut conflictos = Vec::new();
+    for fila in filas.iter().filter(|f| existentes.contains_key(&f.codigo)) {
+        let actual = &existentes[&fila.codigo];
+        conflictos.push(serde_json::json!({
+            "codigo": fila.codigo,
+            "actual_url": actual.get_str("target_url").ok(),
+            "actual_active": actual.get_bool("active").unwrap_or(false),
+            // Sin target_url en el archivo se queda el actual
+            "nueva_url": fila.target_url.as_deref().or(actual.get_str("target_url").ok()),
+            "nueva_active": fila.active,
+        }));
+    }
+
+    let mut resumen = Resumen {
+        filas: filas.len() + errores.len(),
+        validas: filas.len(),
+        existentes: conflictos.len(),
+        errores: errores.len(),
+        ..Default::default()
+    };
+
+    if !dry_run {
+        let ahora = DateTime::now();
+        let mut nuevos = Vec::new();
+
+        for fila in filas {
+            let lote = fila.lote.or(lote_default.clone());
+
+            if existentes.contains_key(&fila.codigo) {
+                if !sobrescribir {
+                    resumen.omitidas += 1;
+                    continue;
+                }
+
+                // Solo lo que trae el archivo: una celda vacía (o sin columna) no borra el destino actual
+                let mut set = doc! {};
+                if let Some(url) = &fila.target_url {
+                    set.insert("target_url", url);
+                }
+                if let Some(lote) = &lote {
+                    set.insert("lote_nombre", lote);
+                }
+
+                // Activar es una transición (puede no estar permitida, ej. un imán perdido);
+                // sin `active` solo se actualizan los datos y el estado se respeta
+                let resultado = if !fila.active && set.is_empty() {
+                    // Nada que cambiar para este código
+                    resumen.omitidas += 1;
+                    continue;
+                } else if fila.active {
+                    set.insert("activated_at", ahora);
+                    estados::transicionar(&state.db, &fila.codigo, EstadoIman::Claimed, None, Some("importado"), set)
+                        .await
+                        .map(|_| ())
+                } else {
+                    collection
+                        .update_one(doc! { "codigo": &fila.codigo }, doc! { "$set": set }, None)
+                        .await
+                        .map(|_| ())
+                        .map_err(estados::ErrorTransicion::Db)
+                };
+
+                match resultado {
+                    Ok(()) => resumen.actualizadas += 1,
+                    Err(estados::ErrorTransicion::Db(e)) => {
+                        return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response();
+                    }
+                    Err(_) => resumen.omitidas += 1,
+                }
+                continue;
+            }
+
+            // Ya están grabados en un tag: entran como "encoded" (o "claimed" si ya tienen destino activo)
+            let estado = if fila.active { EstadoIman::Claimed } else { EstadoIman::Encoded };
+            nuevos.push(Iman {
+                codigo: fila.codigo,
+                target_url: fila.target_url,
+                active: fila.active,
+                activated_at: if fila.active { Some(ahora) } else { None },
+                // No tiene sentido que salgan en "exportar nuevos"
+                exported: true,
+                lote_fecha: lote.as_ref().map(|_| ahora),
+                lote_nombre: lote,
+                estado,
+                historial: vec![CambioEstado { estado, at: ahora, motivo: Some("importado".to_string()) }],
+                ..Default::default()
+            });
+        }
+
+        resumen.nuevas = nuevos.len();
+        for chunk in nuevos.chunks(500) {
+            if let Err(e) = collection.insert_many(chunk, None).await {
+                return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response();
+            }
+        }
+    } else {
+        resumen.nuevas = resumen.validas - resumen.existentes;
+        // Guardamos el contenido para que "Aplicar" no obligue a subir el archivo otra vez
+        context.insert("contenido", &contenido);
+    }
+
+    context.insert("resumen", &resumen);
+    context.insert("errores", &errores);
+    context.insert("conflictos", &conflictos);
+    render_importar(&state, context)
+}
@@ -0,0 +1,217 @@
+// src/routes/lotes.rs
+// Configuración por lote: lo que aplica a todos sus imanes al escanearlos.
+use std::collections::HashMap;
+use std::sync::{LazyLock, RwLock};
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{Form, Path, Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use mongodb::bson::{doc, DateTime, Document};
+use serde::Deserialize;
+use tower_cookies::Cookies;
+
+use crate::{destinos, models::{ConfigLote, Iman, TipoRedireccion}, AppState};
+
+#[derive(Deserialize)]
+pub struct ConfigLoteForm {
+    #[serde(default)]
+    utm: String,
+    redireccion: TipoRedireccion,
+    // Llega como texto para responder con un error amigable si no es número
+    #[serde(default)]
+    intersticial_ms: String,
+}
+
+#[derive(Deserialize)]
+pub struct LoteQuery {
+    // El lote exacto (mismo `ts` que las exportaciones); sin él, el más reciente con ese nombre
+    ts: Option<i64>,
+}
+
+// Ni tan corto que no se alcance a ver, ni tan largo que el cliente se desespere
+const INTERSTICIAL_MIN_MS: u32 = 500;
+const INTERSTICIAL_MAX_MS: u32 = 10_000;
+
+// Cada escaneo de un imán de lote pide su configuración (aunque el lote no tenga):
+// se guarda un rato en memoria, también cuando no hay. Otras réplicas tardan hasta
+// TTL_CONFIG en ver un cambio.
+const TTL_CONFIG: Duration = Duration::from_secs(30);
+const MAX_CACHE_CONFIG: usize = 1_000;
+
+// (nombre, fecha en ms) -> (configuración, cuándo se leyó)
+type CacheConfig = HashMap<(String, Option<i64>), (Option<ConfigLote>, Instant)>;
+static CACHE_CONFIG: LazyLock<RwLock<CacheConfig>> = LazyLock::new(|| RwLock::new(HashMap::new()));
+
+// Los imanes de un lote (sin fecha = imanes viejos sin lote_fecha)
+fn filtro(lote_nombre: &str, lote_fecha: Option<DateTime>) -> Document {
+    doc! { "lote_nombre": lote_nombre, "lote_fecha": lote_fecha }
+}
+
+/// La configuración guardada del lote (None si nunca se ha configurado o si falla la BD:
+/// un escaneo no debe caerse por esto)
+pub(super) async fn config_lote(state: &AppState, lote_nombre: &str, lote_fecha: Option<DateTime>) -> Option<ConfigLote> {
+    let clave = (lote_nombre.to_string(), lote_fecha.map(|f| f.timestamp_millis()));
+    if let Some((config, at)) = CACHE_CONFIG.read().unwrap_or_else(|e| e.into_inner()).get(&clave)
+        && at.elapsed() < TTL_CONFIG
+    {
+        return config.clone();
+    }
+
+    // La del lote exacto o, si no hay, una de antes sin fecha (null ordena al final)
+    let options = mongodb::options::FindOneOptions::builder().sort(doc! { "lote_fecha": -1 }).build();
+    let config = match state
+        .db
+        .collection::<ConfigLote>("lotes_config")
+        .find_one(doc! { "lote_nombre": lote_nombre, "lote_fecha": { "$in": [lote_fecha, null] } }, options)
+        .await
+    {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("❌ Error leyendo configuración del lote {}: {}", lote_nombre, e);
+            return None;
+        }
+    };
+
+    let mut cache = CACHE_CONFIG.write().unwrap_or_else(|e| e.into_inner());
+    if cache.len() >= MAX_CACHE_CONFIG {
+        cache.retain(|_, (_, at)| at.elapsed() < TTL_CONFIG);
+    }
+    cache.insert(clave, (config.clone(), Instant::now()));
+    config
+}
+
+// Fecha del lote: la del `ts` o la del lote más reciente con ese nombre
+async fn fecha_lote(state: &AppState, lote_nombre: &str, ts: Option<i64>) -> Result<Option<DateTime>, Response> {
+    if let Some(ts) = ts {
+        return Ok(Some(DateTime::from_millis(ts)));
+    }
+    let options = mongodb::options::FindOneOptions::builder().sort(doc! { "lote_fecha": -1 }).build();
+    match state.db.collection::<Iman>("imanes").find_one(doc! { "lote_nombre": lote_nombre }, options).await {
+        Ok(Some(iman)) => Ok(iman.lote_fecha),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "❌ Lote no encontrado").into_response()),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response()),
+    }
+}
+
+async fn render_lote(state: &AppState, lote_nombre: &str, lote_fecha: Option<DateTime>, extra: tera::Context) -> Response {
+    let imanes = state.db.collection::<Iman>("imanes");
+    let total = match imanes.count_documents(filtro(lote_nombre, lote_fecha), None).await {
+        Ok(0) => return (StatusCode::NOT_FOUND, "❌ Lote no encontrado").into_response(),
+        Ok(n) => n,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response(),
+    };
+    let config = config_lote(state, lote_nombre, lote_fecha).await.unwrap_or_default();
+
+    // Vista previa con un imán real del lote
+    let ejemplo = match (&config.utm, imanes.find_one(filtro(lote_nombre, lote_fecha), None).await) {
+        (Some(utm), Ok(Some(iman))) => Some(destinos::con_utm("https://instagram.com/tu_usuario", utm, &iman)),
+        _ => None,
+    };
+
+    let mut context = tera::Context::new();
+    context.insert("lote_nombre", lote_nombre);
+    context.insert("ts", &lote_fecha.map(|f| f.timestamp_millis()));
+    context.insert("fecha", &lote_fecha.and_then(|f| chrono::DateTime::from_timestamp_millis(f.timestamp_millis())).map(|f| f.format("%d/%m/%Y %H:%M").to_string()));
+    context.insert("total", &total);
+    context.insert("utm", &config.utm);
+    context.insert("redireccion", &config.redireccion);
+    context.insert("intersticial_ms", &config.intersticial_ms);
+    context.insert("ejemplo_utm", &ejemplo);
+    context.extend(extra);
+
+    match state.tera.render("lote.html", &context) {
+        Ok(html) => Html(html).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+    }
+}
+
+// GET /admin/lotes/:lote_nombre
+pub async fn lote_page(
+    cookies: Cookies,
+    State(state): State<AppState>,
+    Path(lote_nombre): Path<String>,
+    Query(query): Query<LoteQuery>,
+) -> Response {
+    if cookies.get("admin_session").is_none() {
+        return Redirect::to("/login").into_response();
+    }
+
+    match fecha_lote(&state, &lote_nombre, query.ts).await {
+        Ok(lote_fecha) => render_lote(&state, &lote_nombre, lote_fecha, tera::Context::new()).await,
+        Err(response) => response,
+    }
+}
+
+// POST /api/admin/lotes/:lote_nombre/config
+pub async fn guardar_config_lote(
+    cookies: Cookies,
+    State(state): State<AppState>,
+    Path(lote_nombre): Path<String>,
+    Query(query): Query<LoteQuery>,
+    Form(form): Form<ConfigLoteForm>,
+) -> Response {
+    if cookies.get("admin_session").is_none() {
+        return Redirect::to("/login").into_response();
+    }
+
+    let lote_fecha = match fecha_lote(&state, &lote_nombre, query.ts).await {
+        Ok(f) => f,
+        Err(response) => return response,
+    };
+
+    let intersticial_ms = match form.intersticial_ms.trim() {
+        "" => Ok(None),
+        ms => match ms.parse::<u32>() {
+            Ok(ms) if (INTERSTICIAL_MIN_MS..=INTERSTICIAL_MAX_MS).contains(&ms) => Ok(Some(ms)),
+            _ => Err("⚠️ La página intermedia dura de 500 a 10000 ms."),
+        },
+    };
+
+    let mut extra = tera::Context::new();
+    let (utm, intersticial_ms) = match destinos::validar_utm(&form.utm).and_then(|utm| Ok((utm, intersticial_ms?))) {
+        Ok(v) => v,
+        Err(msg) => {
+            extra.insert("error", msg);
+            let mut response = render_lote(&state, &lote_nombre, lote_fecha, extra).await;
+            if response.status() == StatusCode::OK {
+                *response.status_mut() = StatusCode::UNPROCESSABLE_ENTITY;
+            }
+            return response;
+        }
+    };
+
+    let Ok(redireccion) = mongodb::bson::to_bson(&form.redireccion) else {
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Error serializando la configuración").into_response();
+    };
+    let mut set = doc! { "lote_nombre": &lote_nombre, "lote_fecha": lote_fecha, "redireccion": redireccion };
+    let mut unset = doc! {};
+    match utm {
+        Some(utm) => set.insert("utm", utm),
+        None => unset.insert("utm", ""),
+    };
+    match intersticial_ms {
+        Some(ms) => set.insert("intersticial_ms", ms),
+        None => unset.insert("intersticial_ms", ""),
+    };
+    let mut update = doc! { "$set": set };
+    if !unset.is_empty() {
+        update.insert("$unset", unset);
+    }
+
+    let options = mongodb::options::UpdateOptions::builder().upsert(true).build();
+    if let Err(e) = state
+        .db
+        .collection::<Document>("lotes_config")
+        .update_one(filtro(&lote_nombre, lote_fecha), update, options)
+        .await
+    {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response();
+    }
+    CACHE_CONFIG.write().unwrap_or_else(|e| e.into_inner()).retain(|(nombre, _), _| *nombre != lote_nombre);
+
+    extra.insert("mensaje", "✅ Configuración guardada. Aplica en menos de un minuto.");
+    render_lote(&state, &lote_nombre, lote_fecha, extra).await
+}
@@ -1,9 +1,28 @@
+mod dueno;
+mod exportaciones;
+mod imanes;
+mod importar;
+mod lotes;
+mod nfc;
+mod qr;
+mod reportes;
+mod transiciones;
+pub use dueno::*;
+pub use exportaciones::*;
+pub use imanes::*;
+pub use importar::*;
+pub use lotes::*;
+pub use nfc::*;
+pub use qr::*;
+pub use reportes::*;
+pub use transiciones::*;
+
 use futures::stream::TryStreamExt;
 use tower_cookies::{Cookies, Cookie};
 use tokio::time::{sleep, Duration};
 use time::OffsetDateTime;
-use axum::http::header;
-use mongodb::bson::DateTime;
+use axum::http::{header, HeaderMap, HeaderValue};
+use mongodb::bson::{oid::ObjectId, DateTime};
 use chrono::{Datelike, Utc};
 
 use axum::{
@@ -17,19 +36,64 @@ use mongodb::{
 };
 use serde::Deserialize;
 
-use crate::{models::Iman, AppState};
+use crate::{codes, db, destinos, estados, export, hojas, jobs, models::{ClaveIdempotencia, ConfigLote, Contacto, EstadoIman, EstadoJob, EstadoUso, Iman, JobLote, Landing, ModoIman, TipoRedireccion, Wifi}, modos, pin, AppState};
 
 #[derive(Deserialize)]
 pub struct SetupForm {
     codigo: String,
+    // "redireccion" (un solo link, lo de siempre), "landing" (varios links), "vcard" (contacto) o "wifi"
+    modo: Option<String>,
+    #[serde(default)]
     target_url: String,
+    // Modo landing
+    #[serde(default)]
+    titulo: String,
+    #[serde(default)]
+    avatar: String,
+    #[serde(default)]
+    instagram: String,
+    #[serde(default)]
+    tiktok: String,
+    #[serde(default)]
+    whatsapp: String,
+    #[serde(default)]
+    youtube: String,
+    #[serde(default)]
+    facebook: String,
+    // Modo vcard
+    #[serde(default)]
+    nombre: String,
+    #[serde(default)]
+    telefono: String,
+    #[serde(default)]
+    email: String,
+    #[serde(default)]
+    empresa: String,
+    #[serde(default)]
+    sitio: String,
+    // Modo wifi
+    #[serde(default)]
+    ssid: String,
+    #[serde(default)]
+    wifi_password: String,
+    #[serde(default)]
+    seguridad: String,
+    // Checkbox: solo llega si está marcado
+    oculta: Option<String>,
 }
 
 // Maneja GET /v/:codigo
 pub async fn redirect_handler(
     State(state): State<AppState>,
     Path(codigo): Path<String>,
+    headers: HeaderMap,
+    cookies: Cookies,
 ) -> Response {
+    // FILTRO PREVIO: Si el código no pasa el dígito verificador, ni molestamos a Mongo
+    if !codes::es_valido(&codigo) {
+        return render_404(&state);
+    }
+
     let collection = state.db.collection::<Iman>("imanes");
 
     // LÓGICA PRO: "Busca y actualiza" atómicamente
@@ -52,18 +116,76 @@ pub async fn redirect_handler(
     {
         Ok(Some(iman)) => {
            // ¡Encontrado y contador actualizado! ✅
-            if iman.active {
-                if let Some(url) = iman.target_url {
-                    if !url.is_empty() {
-                        // Si tiene URL -> Redirigir
-                        return Redirect::temporary(&url).into_response();
+            match iman.estado {
+                EstadoIman::Claimed if iman.modo == ModoIman::Landing => {
+                    if let Some(landing) = &iman.landing {
+                        return render_pagina_landing(&state, &codigo, landing);
                     }
                 }
+                EstadoIman::Claimed if iman.modo == ModoIman::Vcard => {
+                    if let Some(contacto) = &iman.contacto {
+                        return vcard_response(&codigo, contacto);
+                    }
+                }
+                EstadoIman::Claimed if iman.modo == ModoIman::Wifi => {
+                    if let Some(wifi) = &iman.wifi {
+                        return render_wifi(&state, &codigo, wifi);
+                    }
+                }
+                EstadoIman::Claimed => {
+                    // Programación vigente, regla por teléfono/idioma, prueba A/B o el destino de siempre
+                    let cliente = destinos::Cliente::desde_headers(&headers);
+                    let nombre_cookie = format!("ab_{}", codigo);
+                    let asignada = cookies.get(&nombre_cookie).map(|c| c.value().to_string());
+                    if let Some(destino) = destinos::destino_para(&iman, &cliente, asignada.as_deref(), DateTime::now()) {
+                        if let Some(variante) = destino.variante {
+                            contar_variante(&state, &codigo, variante.id).await;
+                            // Fija la variante para que el mismo visitante vea siempre la misma
+                            if asignada.as_deref() != Some(variante.id.to_hex().as_str()) {
+                                let mut cookie = Cookie::new(nombre_cookie, variante.id.to_hex());
+                                cookie.set_path(format!("/v/{}", codigo));
+                                cookie.set_http_only(true);
+                                cookie.set_max_age(time::Duration::days(90));
+                                cookies.add(cookie);
+                            }
+                        }
+
+                        // UTM del imán o, si no tiene, la de su lote
+                        let config = match &iman.lote_nombre {
+                            Some(lote) => lotes::config_lote(&state, lote, iman.lote_fecha).await.unwrap_or_default(),
+                            None => ConfigLote::default(),
+                        };
+                        let url = match iman.utm.as_ref().or(config.utm.as_ref()) {
+                            Some(utm) => destinos::con_utm(destino.url, utm, &iman),
+                            None => destino.url.to_string(),
+                        };
+
+                        let mut response = match destino.deep_link {
+                            Some(deep_link) => render_deep_link(&state, deep_link, &url),
+                            None => redirigir(&state, &iman, &config, &url),
+                        };
+                        // El destino depende de quién escanea: ningún caché debe reusarlo para otro
+                        if !iman.reglas.is_empty() || !iman.variantes.is_empty() {
+                            response.headers_mut().insert(header::VARY, HeaderValue::from_static("User-Agent, Accept-Language, Cookie"));
+                        }
+                        return response;
+                    }
+                }
+                // Apagado: no redirige ni se puede volver a reclamar (el destino se conserva)
+                EstadoIman::Paused => return render_pausado(&state, &iman),
+                EstadoIman::Disabled => return render_landing(&state, "bloqueado.html", &codigo, StatusCode::FORBIDDEN),
+                EstadoIman::Lost => return render_404(&state),
+                _ => {}
             }
 
-            // Si no está activo o no tiene URL -> Renderizar Setup
-            // (Nota: Si quieres que cuente visitas incluso si no está configurado, déjalo así.
-            //  Si solo quieres contar cuando ya redirige, mueve la lógica de update dentro del if).
+            // Ya tiene dueño pero ahora mismo no hay a dónde mandarlo (solo programación
+            // fuera de su horario, destino borrado...): el setup solo daría un 409
+            if iman.estado == EstadoIman::Claimed {
+                return render_landing(&state, "sin_destino.html", &codigo, StatusCode::SERVICE_UNAVAILABLE);
+            }
+
+            // Sin dueño todavía -> Renderizar Setup
+            // (Nota: Las visitas cuentan aunque no esté configurado; así sabemos qué se escaneó sin reclamar).
             
             let mut context = tera::Context::new();
             context.insert("codigo", &codigo);
@@ -79,13 +201,7 @@ pub async fn redirect_handler(
         }
         Ok(None) => {
             // CASO B: El imán NO existe -> Mostrar plantilla 404 bonita
-            let context = tera::Context::new(); 
-            // (Podrías pasar variables si quisieras, por ahora vacío)
-            
-            match state.tera.render("404.html", &context) {
-                Ok(html) => (StatusCode::NOT_FOUND, Html(html)).into_response(),
-                Err(_) => (StatusCode::NOT_FOUND, "❌ Imán no válido").into_response()
-            }
+            render_404(&state)
         },
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -95,55 +211,298 @@ pub async fn redirect_handler(
     }
 }
 
-// Maneja POST /api/setup
-pub async fn save_iman(
-    State(state): State<AppState>,
-    Form(form): Form<SetupForm>,
-) -> Response {
-    let collection = state.db.collection::<Iman>("imanes");
+// Plantilla 404 bonita (código inexistente o mal formado)
+fn render_404(state: &AppState) -> Response {
+    let context = tera::Context::new();
+    // (Podrías pasar variables si quisieras, por ahora vacío)
+
+    match state.tera.render("404.html", &context) {
+        Ok(html) => (StatusCode::NOT_FOUND, Html(html)).into_response(),
+        Err(_) => (StatusCode::NOT_FOUND, "❌ Imán no válido").into_response()
+    }
+}
 
-    // 1. LIMPIEZA DE INPUT (Sanitization)
-    let url_limpia = form.target_url.trim();
+// Páginas de imán apagado (pausado por su dueño o bloqueado por admin)
+// Pausado por su dueño o, si tiene alerta de seguridad (reportes, revisión periódica),
+// en revisión: eso no lo puede levantar el dueño con su PIN
+fn render_pausado(state: &AppState, iman: &Iman) -> Response {
+    let mut context = tera::Context::new();
+    context.insert("codigo", &iman.codigo);
+    context.insert("en_revision", &iman.alerta_seguridad.is_some());
 
-    // 2. VALIDACIÓN DE SEGURIDAD (Anti-Porno / Anti-Phishing básico)
-    // Forzamos HTTPS y bloqueamos dominios raros si quisieras
-    if !url_limpia.starts_with("https://") {
-         return (StatusCode::BAD_REQUEST, "❌ Por seguridad, solo aceptamos enlaces seguros (https://)").into_response();
+    match state.tera.render("pausado.html", &context) {
+        Ok(html) => (StatusCode::SERVICE_UNAVAILABLE, Html(html)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Template error: {}", e)).into_response(),
     }
-    
-    // Validación extra: Que parezca una URL real de redes sociales (Opcional pero recomendado)
-    // Esto evita que pongan "https://mi-sitio-de-virus.com"
-    let dominios_permitidos = ["instagram.com", "tiktok.com", "facebook.com", "youtube.com", "twitter.com", "x.com"];
-    let es_seguro = dominios_permitidos.iter().any(|d| url_limpia.contains(d));
+}
+
+fn render_landing(state: &AppState, plantilla: &str, codigo: &str, status: StatusCode) -> Response {
+    let mut context = tera::Context::new();
+    context.insert("codigo", codigo);
 
-    if !es_seguro {
-        // OJO: Puedes quitar esto si quieres permitir cualquier web, 
-        // pero dejarlo reduce riesgo de sitios maliciosos.
-        return (StatusCode::BAD_REQUEST, "⚠️ Por ahora solo permitimos redes sociales reconocidas (TikTok, Instagram, Youtube, etc).").into_response();
+    match state.tera.render(plantilla, &context) {
+        Ok(html) => (status, Html(html)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Template error: {}", e)).into_response(),
     }
+}
 
-    // 3. ACTUALIZACIÓN EN MONGO
-    let filter = doc! { "codigo": &form.codigo };
+// Página "link in bio" de un imán en modo landing
+fn render_pagina_landing(state: &AppState, codigo: &str, landing: &Landing) -> Response {
+    let mut context = tera::Context::new();
+    context.insert("codigo", codigo);
+    context.insert("landing", landing);
 
-    let update = doc! { 
-        "$set": {
-            "target_url": url_limpia,
-            "active": true,
-            "activated_at": DateTime::now(), // ¡Marca de tiempo actual!
-            "last_scan_at": DateTime::now()  // También cuenta como primer scan
+    match state.tera.render("landing.html", &context) {
+        Ok(html) => Html(html).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Template error: {}", e)).into_response(),
+    }
+}
+
+// Imán de tarjeta de presentación: el teléfono ofrece agregar el contacto
+fn vcard_response(codigo: &str, contacto: &Contacto) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, "text/vcard; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}.vcf\"", codigo)),
+            // Si el dueño cambia sus datos, que no se quede la versión vieja en caché
+            (header::CACHE_CONTROL, "no-store".to_string()),
+        ],
+        modos::vcard(contacto),
+    )
+        .into_response()
+}
+
+// Duración por defecto de la página intermedia
+const INTERSTICIAL_MS_DEFAULT: u32 = 2500;
+
+// Manda al destino como lo pide la configuración del lote
+fn redirigir(state: &AppState, iman: &Iman, config: &ConfigLote, url: &str) -> Response {
+    // Un 308 se puede quedar en la caché del navegador: si el destino cambia según
+    // quién o cuándo escanea, lo bajamos a 307 para no fijar uno para siempre
+    let dinamico = !iman.programacion.is_empty() || !iman.reglas.is_empty() || !iman.variantes.is_empty();
+
+    match config.redireccion {
+        TipoRedireccion::Found => match HeaderValue::try_from(url) {
+            Ok(location) => (StatusCode::FOUND, [(header::LOCATION, location)]).into_response(),
+            Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "❌ Destino inválido").into_response(),
+        },
+        TipoRedireccion::Permanente if !dinamico => Redirect::permanent(url).into_response(),
+        TipoRedireccion::Intersticial => {
+            let mut context = tera::Context::new();
+            context.insert("codigo", &iman.codigo);
+            context.insert("url", url);
+            // Para el aviso de "vas a salir a ..."
+            context.insert("dominio", &url::Url::parse(url).ok().and_then(|u| u.host_str().map(str::to_string)));
+            context.insert("ms", &config.intersticial_ms.unwrap_or(INTERSTICIAL_MS_DEFAULT));
+
+            match state.tera.render("intersticial.html", &context) {
+                Ok(html) => Html(html).into_response(),
+                // Sin plantilla, al menos que llegue al destino
+                Err(_) => Redirect::temporary(url).into_response(),
+            }
         }
+        TipoRedireccion::Temporal | TipoRedireccion::Permanente => Redirect::temporary(url).into_response(),
+    }
+}
+
+// Suma el escaneo a la variante A/B que se sirvió. Si falla no tiramos el redirect por eso.
+async fn contar_variante(state: &AppState, codigo: &str, variante: ObjectId) {
+    let filter = doc! { "codigo": codigo, "variantes.id": variante };
+    let update = doc! { "$inc": { "variantes.$.visitas": 1 } };
+    if let Err(e) = state.db.collection::<Iman>("imanes").update_one(filter, update, None).await {
+        eprintln!("❌ Error contando variante de {}: {}", codigo, e);
+    }
+}
+
+// Intenta abrir la app y, si no está instalada, manda al destino web
+fn render_deep_link(state: &AppState, deep_link: &str, url: &str) -> Response {
+    let mut context = tera::Context::new();
+    context.insert("deep_link", deep_link);
+    context.insert("url", url);
+
+    match state.tera.render("deeplink.html", &context) {
+        Ok(html) => Html(html).into_response(),
+        // Sin plantilla, al menos que llegue al destino web
+        Err(_) => Redirect::temporary(url).into_response(),
+    }
+}
+
+// Página con los datos de la red y el QR para unirse sin teclear la contraseña
+fn render_wifi(state: &AppState, codigo: &str, wifi: &Wifi) -> Response {
+    let qr_svg = match crate::qr::svg(&modos::wifi_qr(wifi), 240) {
+        Ok(svg) => svg,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Error generando QR: {}", e)).into_response(),
+    };
+
+    let mut context = tera::Context::new();
+    context.insert("codigo", codigo);
+    context.insert("wifi", wifi);
+    context.insert("qr_svg", &qr_svg);
+
+    match state.tera.render("wifi.html", &context) {
+        // La contraseña va en la página: que ningún proxy la guarde
+        Ok(html) => ([(header::CACHE_CONTROL, "no-store")], Html(html)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Template error: {}", e)).into_response(),
+    }
+}
+
+// Maneja GET /v/:codigo/l/:indice (clic en un link de la landing)
+pub async fn landing_clic(
+    State(state): State<AppState>,
+    Path((codigo, indice)): Path<(String, usize)>,
+) -> Response {
+    if !codes::es_valido(&codigo) {
+        return render_404(&state);
+    }
+
+    // Contamos el clic y de paso obtenemos a dónde va (el índice tiene que existir)
+    let filter = doc! {
+        "codigo": &codigo,
+        "estado": estados::clave(EstadoIman::Claimed),
+        "modo": "landing",
+        format!("landing.enlaces.{}", indice): { "$exists": true },
     };
+    let update = doc! { "$inc": { format!("landing.enlaces.{}.clics", indice): 1 } };
 
-    match collection.update_one(filter, update, None).await {
-        Ok(_) => {
-            let ruta_magica = format!("/v/{}", form.codigo);
-            Redirect::to(&ruta_magica).into_response()
+    match state.db.collection::<Iman>("imanes").find_one_and_update(filter, update, None).await {
+        Ok(Some(iman)) => match iman.landing.as_ref().and_then(|l| l.enlaces.get(indice)) {
+            Some(enlace) => Redirect::temporary(&enlace.url).into_response(),
+            None => render_404(&state),
+        },
+        // Pausado, bloqueado o ya sin ese link: que /v/ decida qué mostrar
+        Ok(None) => Redirect::to(&format!("/v/{}", codigo)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response(),
+    }
+}
+
+// Maneja POST /api/setup
+pub async fn save_iman(
+    State(state): State<AppState>,
+    Form(form): Form<SetupForm>,
+) -> Response {
+    let ahora = DateTime::now();
+    let mut extra = doc! {
+        "activated_at": ahora, // ¡Marca de tiempo actual!
+        "last_scan_at": ahora  // También cuenta como primer scan
+    };
+
+    // 1. LIMPIEZA Y VALIDACIÓN según el modo (Anti-Porno / Anti-Phishing básico)
+    // `urls` junta todo lo que el imán va a abrir, para pasarlo por la revisión de seguridad
+    let mut urls: Vec<String> = Vec::new();
+    let destino = match form.modo.as_deref().unwrap_or("redireccion") {
+        "redireccion" => {
+            let url_limpia = form.target_url.trim();
+            if let Err(msg) = destinos::validar_destino(url_limpia) {
+                return (StatusCode::BAD_REQUEST, msg).into_response();
+            }
+            extra.insert("modo", "redireccion");
+            extra.insert("target_url", url_limpia);
+            urls.push(url_limpia.to_string());
+            url_limpia.to_string()
         }
-        Err(e) => (
+        "landing" => {
+            let redes = [
+                ("instagram", form.instagram.as_str()),
+                ("tiktok", form.tiktok.as_str()),
+                ("whatsapp", form.whatsapp.as_str()),
+                ("youtube", form.youtube.as_str()),
+                ("facebook", form.facebook.as_str()),
+            ];
+            let landing = match modos::armar_landing(&form.titulo, &form.avatar, redes) {
+                Ok(l) => l,
+                Err(msg) => return (StatusCode::BAD_REQUEST, msg).into_response(),
+            };
+            urls.extend(landing.enlaces.iter().map(|e| e.url.clone()));
+            let Ok(landing) = mongodb::bson::to_bson(&landing) else {
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Error serializando la página").into_response();
+            };
+            extra.insert("modo", "landing");
+            extra.insert("landing", landing);
+            format!("/v/{}", form.codigo)
+        }
+        "vcard" => {
+            let enlaces = [("sitio", form.sitio.as_str()), ("instagram", form.instagram.as_str())];
+            let contacto = match modos::armar_contacto(&form.nombre, &form.telefono, &form.email, &form.empresa, enlaces) {
+                Ok(c) => c,
+                Err(msg) => return (StatusCode::BAD_REQUEST, msg).into_response(),
+            };
+            urls.extend(contacto.enlaces.iter().cloned());
+            let Ok(contacto) = mongodb::bson::to_bson(&contacto) else {
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Error serializando el contacto").into_response();
+            };
+            extra.insert("modo", "vcard");
+            extra.insert("contacto", contacto);
+            format!("/v/{}", form.codigo)
+        }
+        "wifi" => {
+            let wifi = match modos::armar_wifi(&form.ssid, &form.wifi_password, &form.seguridad, form.oculta.is_some()) {
+                Ok(w) => w,
+                Err(msg) => return (StatusCode::BAD_REQUEST, msg).into_response(),
+            };
+            let Ok(wifi) = mongodb::bson::to_bson(&wifi) else {
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Error serializando la red").into_response();
+            };
+            extra.insert("modo", "wifi");
+            extra.insert("wifi", wifi);
+            format!("/v/{}", form.codigo)
+        }
+        _ => return (StatusCode::BAD_REQUEST, "❌ Modo no válido").into_response(),
+    };
+
+    // 2. REVISIÓN DE SEGURIDAD: lista negra e imitaciones de dominios (ver crate::seguridad)
+    if let Some((url, motivo)) = state.seguridad.primera_bloqueada(urls.iter().map(String::as_str)).await {
+        eprintln!("🚩 Reclamo de {} rechazado: {} ({})", form.codigo, url, motivo);
+        return (StatusCode::BAD_REQUEST, "❌ Ese enlace está marcado como inseguro. Revisa que sea el sitio correcto.").into_response();
+    }
+
+    // 3. RECLAMO: Solo imanes sin dueño (si ya lo reclamó alguien, no se le cambia el destino)
+
+    let iman = match estados::transicionar(&state.db, &form.codigo, EstadoIman::Claimed, Some(&estados::SIN_DUENO), None, extra).await {
+        Ok(iman) => iman,
+        Err(estados::ErrorTransicion::NoExiste) => return render_404(&state),
+        Err(estados::ErrorTransicion::NoPermitida { .. }) => {
+            return (StatusCode::CONFLICT, "❌ Este imán ya tiene dueño.").into_response();
+        }
+        Err(e) => return (
             StatusCode::INTERNAL_SERVER_ERROR,
             format!("Error guardando: {}", e),
         )
             .into_response(),
+    };
+
+    // 4. PIN DEL DUEÑO: Si no venía impreso en la etiqueta, le damos uno ahora.
+    // Se guarda solo el hash: esta es la única vez que se muestra.
+    let pin = match (iman.owner_pin, iman.owner_pin_hash) {
+        (Some(pin), _) => Some(pin),
+        // Ya tenía uno con hash: no lo podemos (ni queremos) mostrar
+        (None, Some(_)) => None,
+        (None, None) => {
+            let pin = pin::aleatorio();
+            let para_hash = pin.clone();
+            let Ok(hash) = tokio::task::spawn_blocking(move || pin::hash(&para_hash)).await else {
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Error generando el PIN").into_response();
+            };
+            let filter = doc! {
+                "codigo": &form.codigo,
+                "owner_pin": { "$exists": false },
+                "owner_pin_hash": { "$exists": false },
+            };
+            let update = doc! { "$set": { "owner_pin_hash": hash } };
+            if let Err(e) = state.db.collection::<Iman>("imanes").update_one(filter, update, None).await {
+                return (StatusCode::INTERNAL_SERVER_ERROR, format!("Error guardando: {}", e)).into_response();
+            }
+            Some(pin)
+        }
+    };
+
+    let mut context = tera::Context::new();
+    context.insert("codigo", &form.codigo);
+    context.insert("pin", &pin);
+    context.insert("target_url", &destino);
+
+    match state.tera.render("reclamado.html", &context) {
+        Ok(html) => Html(html).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Template error: {}", e)).into_response(),
     }
 }
 
@@ -151,12 +510,17 @@ pub async fn save_iman(
 #[derive(Deserialize)]
 pub struct LoginForm {
     password: String,
+    // Nombre de quien opera el panel (solo para el historial de exportaciones)
+    operador: Option<String>,
 }
 
 #[derive(Deserialize)]
 pub struct GenerateLoteRequest {
-    cantidad: i32,
+    // Llega como texto para poder responder con un error amigable si no es número
+    cantidad: String,
     nombre_lote: String,
+    // Campo oculto del formulario; evita lotes duplicados por doble click o reintentos
+    idempotency_key: Option<String>,
 }
 
 // 1. Mostrar pantalla de Login (GET /login)
@@ -184,6 +548,14 @@ pub async fn process_login(
         
         cookies.add(cookie);
 
+        let operador = form.operador.as_deref().map(str::trim).filter(|o| !o.is_empty()).unwrap_or("admin");
+        let mut cookie = Cookie::new("admin_operador", operador.to_string());
+        cookie.set_path("/");
+        cookie.set_http_only(true);
+        cookie.set_secure(true);
+        cookie.set_max_age(time::Duration::hours(1));
+        cookies.add(cookie);
+
         Redirect::to("/admin").into_response()
     } else {
         // 🛡️ DEFENSA CONTRA FUERZA BRUTA
@@ -207,7 +579,13 @@ pub async fn admin_dashboard(
         // Si no hay cookie, ¡fuera! Al login.
         return Redirect::to("/login").into_response();
     }
-    
+
+    render_admin(&state, tera::Context::new()).await
+}
+
+// Arma el contexto completo del dashboard. `extra` permite inyectar avisos
+// (errores del formulario, mensajes de éxito) sin duplicar todo el cálculo.
+async fn render_admin(state: &AppState, extra: tera::Context) -> Response {
     let collection = state.db.collection::<Iman>("imanes");
 
     // Traemos TODOS los imanes para hacer conteo en memoria
@@ -245,7 +623,7 @@ pub async fn admin_dashboard(
 
     for iman in &all_imanes {
         
-        if iman.active {
+        if iman.estado == EstadoIman::Claimed {
             activos_total += 1;
             
             // Checamos fecha de activación
@@ -260,7 +638,7 @@ pub async fn admin_dashboard(
 
                     // Chart logic
                     let months_diff = (now.year() - fecha.year()) * 12 + (now.month() - fecha.month()) as i32;
-                    if months_diff >= 0 && months_diff < 6 {
+                    if (0..6).contains(&months_diff) {
                         // Invertimos el índice porque history_counts[5] es el mes actual
                         let index = 5 - months_diff as usize;
                         history_counts[index] += 1;
@@ -275,7 +653,7 @@ pub async fn admin_dashboard(
     }
 
     // Ordenamos el vector para sacar el Top 10 manual
-    top_imanes.sort_by(|a, b| b.visitas.cmp(&a.visitas));
+    top_imanes.sort_by_key(|iman| std::cmp::Reverse(iman.visitas));
     let top_10_raw: Vec<Iman> = top_imanes.into_iter().take(10).collect();
     
     // Convertir a formato amigable para Tera (View Model)
@@ -293,13 +671,40 @@ pub async fn admin_dashboard(
         })
     }).collect();
 
-    let disponibles_total = all_imanes.len() - activos_total;
+    // Imanes con prueba A/B corriendo (las más escaneadas primero)
+    let mut con_variantes: Vec<&Iman> = all_imanes.iter().filter(|i| !i.variantes.is_empty()).collect();
+    con_variantes.sort_by_key(|i| std::cmp::Reverse(i.variantes.iter().map(|v| v.visitas).sum::<i64>()));
+    let pruebas_ab: Vec<serde_json::Value> = con_variantes
+        .into_iter()
+        .map(|iman| serde_json::json!({ "codigo": iman.codigo, "variantes": imanes::variantes_view(iman) }))
+        .collect();
+
+    // Imanes que la revisión de seguridad marcó (los más recientes primero)
+    let mut marcados: Vec<&Iman> = all_imanes.iter().filter(|i| i.alerta_seguridad.is_some()).collect();
+    marcados.sort_by_key(|i| std::cmp::Reverse(i.alerta_seguridad.as_ref().map(|a| a.at)));
+    let alertas_seguridad: Vec<serde_json::Value> = marcados
+        .into_iter()
+        .filter_map(|iman| {
+            let alerta = iman.alerta_seguridad.as_ref()?;
+            Some(serde_json::json!({
+                "codigo": iman.codigo,
+                "etiqueta": estados::etiqueta(iman.estado),
+                "url": alerta.url,
+                "motivo": alerta.motivo,
+                "at": chrono::DateTime::from_timestamp_millis(alerta.at.timestamp_millis()).map(|dt| dt.to_rfc3339()),
+            }))
+        })
+        .collect();
+
+    // Disponibles = todavía sin dueño (pausados, desactivados y perdidos no cuentan)
+    let disponibles_total = all_imanes.iter().filter(|i| estados::SIN_DUENO.contains(&i.estado)).count();
     let base_url = std::env::var("BASE_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
 
     // --- CÁLCULO DE LOTES (HISTORIAL) ---
     use std::collections::HashMap;
     // Agrupamos por una clave única: "Nombre|Timestamp"
-    let mut lotes_map: HashMap<String, (String, mongodb::bson::DateTime, i32, i32)> = HashMap::new();
+    // Valor: (nombre, fecha, total, conteo por EstadoUso en el orden de EstadoUso::TODOS)
+    let mut lotes_map: HashMap<String, (String, mongodb::bson::DateTime, i32, [i32; 4])> = HashMap::new();
 
     for iman in &all_imanes {
         if let (Some(lote_n), Some(lote_f)) = (&iman.lote_nombre, &iman.lote_fecha) {
@@ -308,25 +713,33 @@ pub async fn admin_dashboard(
                 lote_n.clone(),
                 *lote_f, 
                 0, 
-                0
+                [0; 4]
             ));
             entry.2 += 1; // total
-            if iman.visitas > 0 {
-                entry.3 += 1; // Asignados (Ya grabados en NFC)
+            let estado = iman.estado_uso();
+            if let Some(i) = EstadoUso::TODOS.iter().position(|e| *e == estado) {
+                entry.3[i] += 1;
             }
         }
     }
 
-    let mut lotes_view: Vec<serde_json::Value> = lotes_map.into_iter().map(|(_, (nombre, fecha, total, asignados))| {
+    let mut lotes_view: Vec<serde_json::Value> = lotes_map.into_iter().map(|(_, (nombre, fecha, total, conteos))| {
         let fecha_iso = chrono::DateTime::from_timestamp_millis(fecha.timestamp_millis())
             .map(|dt| dt.to_rfc3339());
+
+        // { "virgin": n, "scanned": n, "active": n, "disabled": n }
+        let estados: serde_json::Map<String, serde_json::Value> = EstadoUso::TODOS
+            .iter()
+            .zip(conteos)
+            .map(|(e, n)| (e.clave().to_string(), n.into()))
+            .collect();
         
         serde_json::json!({
             "nombre": nombre,
             "fecha": fecha_iso,
             "timestamp": fecha.timestamp_millis(),
             "total": total,
-            "asignados": asignados
+            "estados": estados
         })
     }).collect();
 
@@ -339,6 +752,8 @@ pub async fn admin_dashboard(
     context.insert("activos", &activos_total);
     context.insert("disponibles", &disponibles_total);
     context.insert("top_imanes", &top_10_view);
+    context.insert("pruebas_ab", &pruebas_ab);
+    context.insert("alertas_seguridad", &alertas_seguridad);
     context.insert("lotes", &lotes_view); 
     context.insert("activos_mes", &activos_este_mes);      
     context.insert("activos_mes_ant", &activos_mes_pasado); 
@@ -347,6 +762,24 @@ pub async fn admin_dashboard(
     
     // Pasamos el dominio base para facilitar la grabación de NFCs
     context.insert("base_url", &base_url); 
+    context.insert("max_cantidad", &max_cantidad_lote());
+    let plantillas: Vec<serde_json::Value> = hojas::PLANTILLAS
+        .iter()
+        .map(|p| serde_json::json!({ "clave": p.clave, "nombre": p.nombre }))
+        .collect();
+    context.insert("plantillas", &plantillas);
+    let estados_iman: Vec<serde_json::Value> = [
+        EstadoIman::Generated, EstadoIman::Exported, EstadoIman::Encoded, EstadoIman::Sold,
+        EstadoIman::Claimed, EstadoIman::Paused, EstadoIman::Disabled, EstadoIman::Lost,
+    ]
+        .iter()
+        .map(|e| serde_json::json!({ "clave": estados::clave(*e), "etiqueta": estados::etiqueta(*e) }))
+        .collect();
+    context.insert("estados_iman", &estados_iman);
+    context.insert("jobs", &jobs_recientes(state).await);
+    // Clave nueva por cada render del formulario de fabricación
+    context.insert("idempotency_key", &format!("{:032x}", rand::random::<u128>()));
+    context.extend(extra);
 
     match state.tera.render("admin.html", &context) {
         Ok(html) => Html(html).into_response(),
@@ -356,10 +789,47 @@ pub async fn admin_dashboard(
 
 
 
+// Tope de links por lote (configurable con MAX_CANTIDAD_LOTE)
+fn max_cantidad_lote() -> u32 {
+    std::env::var("MAX_CANTIDAD_LOTE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(50_000)
+}
+
+// Valida el formulario de generación. Regresa (nombre limpio, cantidad) o un mensaje amigable.
+fn validar_lote(payload: &GenerateLoteRequest) -> Result<(String, u32), String> {
+    let nombre = payload.nombre_lote.trim();
+    if nombre.is_empty() {
+        return Err("El nombre del lote no puede estar vacío.".to_string());
+    }
+    if nombre.chars().count() > 40 {
+        return Err("El nombre del lote es muy largo (máximo 40 caracteres).".to_string());
+    }
+    if !nombre.chars().all(|c| c.is_alphanumeric() || c == ' ' || c == '-' || c == '_') {
+        return Err("El nombre del lote solo puede tener letras, números, espacios, guiones y guiones bajos.".to_string());
+    }
+    if codes::slug_lote(nombre).is_empty() {
+        return Err("El nombre del lote necesita al menos una letra o número.".to_string());
+    }
+
+    let max = max_cantidad_lote();
+    let cantidad: u32 = match payload.cantidad.trim().parse() {
+        Ok(n) if n >= 1 => n,
+        _ => return Err("La cantidad debe ser un número entero mayor a cero.".to_string()),
+    };
+    if cantidad > max {
+        return Err(format!("La cantidad máxima por lote es {}. Divide la producción en varios lotes.", max));
+    }
+
+    Ok((nombre.to_string(), cantidad))
+}
+
 // POST /api/admin/generate
 pub async fn generate_batch(
     cookies: Cookies,
     State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
     Form(payload): Form<GenerateLoteRequest>,
 ) -> Response {
     // 1. Seguridad (Cookie Check)
@@ -367,113 +837,255 @@ pub async fn generate_batch(
         return Redirect::to("/login").into_response();
     }
 
-    let collection = state.db.collection::<Iman>("imanes");
-    let mut docs = Vec::new();
-    
-    let now_mongo = DateTime::now();
+    // 2. Validación: si algo está mal, regresamos al panel con el error y lo que escribieron
+    let (nombre_lote, cantidad) = match validar_lote(&payload) {
+        Ok(v) => v,
+        Err(msg) => {
+            let mut extra = tera::Context::new();
+            extra.insert("error_lote", &msg);
+            extra.insert("form_nombre", &payload.nombre_lote);
+            extra.insert("form_cantidad", &payload.cantidad);
+
+            let mut response = render_admin(&state, extra).await;
+            *response.status_mut() = StatusCode::UNPROCESSABLE_ENTITY;
+            return response;
+        }
+    };
+
+    let jobs = state.db.collection::<JobLote>("jobs");
+    let job_id = ObjectId::new();
+
+    // 3. IDEMPOTENCIA: El header Idempotency-Key manda; si no, el campo oculto del form.
+    // Reclamamos la clave ANTES de encolar: si ya existía, regresamos el job original.
+    let clave = headers
+        .get("Idempotency-Key")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+        .or(payload.idempotency_key.clone())
+        .filter(|k| !k.trim().is_empty());
+
+    if let Some(key) = clave {
+        let claves = state.db.collection::<ClaveIdempotencia>("idempotencia");
+        let nueva = ClaveIdempotencia { key: key.clone(), job_id, created_at: DateTime::now() };
+
+        if let Err(e) = claves.insert_one(nueva, None).await {
+            if !db::es_clave_duplicada(&e) {
+                return (StatusCode::INTERNAL_SERVER_ERROR, format!("Error DB: {}", e)).into_response();
+            }
+
+            return match claves.find_one(doc! { "key": &key }, None).await {
+                Ok(Some(previa)) => Redirect::to(&format!("/admin/jobs/{}", previa.job_id.to_hex())).into_response(),
+                // Caducó justo entre el insert y el find: mejor pedir que recarguen que duplicar
+                Ok(None) => (StatusCode::CONFLICT, "⚠️ Esta solicitud ya se procesó. Recarga el panel.").into_response(),
+                Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Error DB: {}", e)).into_response(),
+            };
+        }
+    }
+
     // Usamos segundos desde la época UNIX para garantizar unicidad absoluta en cada ejecución
     let unique_id = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .unwrap()
         .as_secs() % 1000000; // Tomamos los últimos 6 dígitos para que no sea excesivamente largo
-    
-    // Limpiamos el nombre del lote para el código (Quitar espacios y a Mayúsculas)
-    let nombre_lote_slug = payload.nombre_lote.replace(" ", "").to_uppercase();
 
-    for i in 1..=payload.cantidad {
-        // Formato: NOMBRE-UNIQUEID-NUM (Ej: HUASTECA-748291-0001)
-        let codigo = format!("{}-{}-{:04}", nombre_lote_slug, unique_id, i); 
-        
-        docs.push(Iman {
-            id: None,
-            codigo,
-            target_url: None,
-            active: false,
-            visitas: 0,
-            activated_at: None,
-            last_scan_at: None,
-            exported: false,
-            lote_nombre: Some(payload.nombre_lote.clone()),
-            lote_fecha: Some(now_mongo),
-        });
-    }
+    // Limpiamos el nombre del lote para el código (Solo A-Z y 0-9, sin acentos)
+    let prefijo = format!("{}-{}", codes::slug_lote(&nombre_lote), unique_id);
+
+    // 4. Encolamos el trabajo y respondemos de inmediato; la fabricación corre en segundo plano
+    let job = JobLote {
+        id: Some(job_id),
+        estado: EstadoJob::Queued,
+        lote_nombre: nombre_lote,
+        lote_fecha: DateTime::now(),
+        prefijo,
+        cantidad,
+        progreso: 0,
+        instancia: None,
+        latido: None,
+        error: None,
+        created_at: DateTime::now(),
+        finished_at: None,
+    };
 
-    if let Err(e) = collection.insert_many(docs, None).await {
-         return (StatusCode::INTERNAL_SERVER_ERROR, format!("Error DB: {}", e)).into_response();
+    if let Err(e) = jobs.insert_one(job, None).await {
+        // Liberamos la clave para que el reintento sí pueda crear el lote
+        state.db.collection::<ClaveIdempotencia>("idempotencia")
+            .delete_one(doc! { "job_id": job_id }, None)
+            .await
+            .ok();
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("Error DB: {}", e)).into_response();
     }
 
-    Redirect::to("/admin").into_response()
+    jobs::spawn_generacion(state.db.clone(), job_id);
+
+    Redirect::to(&format!("/admin/jobs/{}", job_id.to_hex())).into_response()
 }
 
+// Vista amigable de un job para Tera (evita pelearse con bson::DateTime en el template)
+fn job_view(job: &JobLote) -> serde_json::Value {
+    // En u64: con MAX_CANTIDAD_LOTE grande, progreso * 100 no cabe en u32
+    let porcentaje = (u64::from(job.progreso) * 100).checked_div(u64::from(job.cantidad)).unwrap_or(0);
+
+    serde_json::json!({
+        "id": job.id.map(|id| id.to_hex()),
+        "estado": job.estado,
+        "etiqueta": jobs::etiqueta(job.estado),
+        "lote_nombre": job.lote_nombre,
+        "timestamp": job.lote_fecha.timestamp_millis(),
+        "cantidad": job.cantidad,
+        "progreso": job.progreso,
+        "porcentaje": porcentaje,
+        "error": job.error,
+        "created_at": chrono::DateTime::from_timestamp_millis(job.created_at.timestamp_millis()).map(|dt| dt.to_rfc3339()),
+    })
+}
 
-// GET /auth/logout
-pub async fn logout(cookies: Cookies) -> Response {
-    // Creamos una cookie vacía con fecha de expiración en el pasado
-    let mut cookie = Cookie::new("admin_session", "");
-    cookie.set_path("/");
-    cookie.set_expires(OffsetDateTime::now_utc() - time::Duration::days(1));
-    cookies.add(cookie);
+// Últimos 10 jobs para la tabla del dashboard
+async fn jobs_recientes(state: &AppState) -> Vec<serde_json::Value> {
+    let options = mongodb::options::FindOptions::builder()
+        .sort(doc! { "created_at": -1 })
+        .limit(10)
+        .build();
 
-    Redirect::to("/login").into_response()
+    let mut recientes = Vec::new();
+    if let Ok(mut cursor) = state.db.collection::<JobLote>("jobs").find(doc! {}, options).await {
+        while let Ok(Some(job)) = cursor.try_next().await {
+            recientes.push(job_view(&job));
+        }
+    }
+    recientes
 }
 
-
-// GET /api/admin/export_csv
-pub async fn export_csv(
+// GET /admin/jobs/:id
+pub async fn job_status(
     cookies: Cookies,
     State(state): State<AppState>,
+    Path(id): Path<String>,
 ) -> Response {
     if cookies.get("admin_session").is_none() {
         return Redirect::to("/login").into_response();
     }
 
-    let collection = state.db.collection::<Iman>("imanes");
-    
-    // 1. FILTRO: Solo dame los que NO están activos Y NO han sido exportados
-    // (O usamos $ne: true para incluir los que no tienen el campo todavía)
-    let filter = doc! { 
-        "active": false,
-        "exported": { "$ne": true } 
+    let Ok(oid) = ObjectId::parse_str(&id) else {
+        return (StatusCode::NOT_FOUND, "❌ Job no encontrado").into_response();
     };
 
-    let mut cursor = collection.find(filter.clone(), None).await.unwrap();
-    let mut csv_content = String::from("codigo,url_completa\n");
-    let base_url = std::env::var("BASE_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
-    let mut count = 0;
+    let job = match state.db.collection::<JobLote>("jobs").find_one(doc! { "_id": oid }, None).await {
+        Ok(Some(job)) => job,
+        Ok(None) => return (StatusCode::NOT_FOUND, "❌ Job no encontrado").into_response(),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response(),
+    };
 
-    while let Ok(Some(iman)) = cursor.try_next().await {
-        let linea = format!("{},{}/v/{}\n", iman.codigo, base_url, iman.codigo);
-        csv_content.push_str(&linea);
-        count += 1;
+    let mut context = tera::Context::new();
+    context.insert("job", &job_view(&job));
+
+    match state.tera.render("job.html", &context) {
+        Ok(html) => Html(html).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
     }
+}
+
 
-    if count == 0 {
-        return (StatusCode::OK, "⚠️ No hay imanes nuevos para exportar. Genera un lote primero.").into_response();
+// GET /auth/logout
+pub async fn logout(cookies: Cookies) -> Response {
+    // Creamos cookies vacías con fecha de expiración en el pasado
+    for nombre in ["admin_session", "admin_operador"] {
+        let mut cookie = Cookie::new(nombre, "");
+        cookie.set_path("/");
+        cookie.set_expires(OffsetDateTime::now_utc() - time::Duration::days(1));
+        cookies.add(cookie);
     }
 
-    // 2. ACTUALIZACIÓN MASIVA (Atomic Update)
-    // Marcamos TODOS los que acabamos de encontrar como exported: true
-    // Así la próxima vez, el filtro de arriba ya no los encontrará.
-    let update = doc! { "$set": { "exported": true } };
-    collection.update_many(filter, update, None).await.ok();
+    Redirect::to("/login").into_response()
+}
+
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    pub(crate) ts: Option<i64>,
+    // Columnas separadas por coma (o "all"). Ver export::Columna
+    pub(crate) cols: Option<String>,
+    // ?bom=1 agrega la marca UTF-8 para que Excel respete acentos
+    pub(crate) bom: Option<u8>,
+    // csv (default), xlsx, json o ndjson. Ver export::Formato
+    pub(crate) formato: Option<String>,
+}
+
+// El ?formato= pedido, o el 400 listo para regresar. Quien toca la BD antes
+// de responder (ej. "exportar nuevos") lo revisa primero.
+pub(crate) fn formato_export(query: &ExportQuery) -> Result<export::Formato, (StatusCode, &'static str)> {
+    export::Formato::parse(query.formato.as_deref())
+        .ok_or((StatusCode::BAD_REQUEST, "❌ Formato no soportado. Usa csv, xlsx, json o ndjson."))
+}
+
+// Respuesta en el formato pedido (?formato=), con nombre de archivo seguro.
+// CSV y JSON salen en streaming desde el cursor; XLSX se arma completo.
+pub(crate) async fn export_response(
+    cursor: mongodb::Cursor<Iman>,
+    query: &ExportQuery,
+    nombre_base: &str,
+) -> Response {
+    let formato = match formato_export(query) {
+        Ok(f) => f,
+        Err(error) => return error.into_response(),
+    };
+
+    let base_url = std::env::var("BASE_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
+    let columnas = export::parse_columnas(query.cols.as_deref());
+    let nombre_archivo = format!("{}.{}", nombre_base, formato.extension());
+
+    let cuerpo = match formato {
+        export::Formato::Csv => {
+            axum::body::Body::from_stream(export::stream_csv(cursor, columnas, base_url, query.bom == Some(1)))
+        }
+        export::Formato::Json | export::Formato::Ndjson => {
+            axum::body::Body::from_stream(export::stream_json(cursor, columnas, base_url, formato == export::Formato::Ndjson))
+        }
+        export::Formato::Xlsx => {
+            let imanes: Vec<Iman> = match cursor.try_collect().await {
+                Ok(i) => i,
+                Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response(),
+            };
+            // Armar el ZIP es CPU pura: fuera del runtime async
+            let libro = tokio::task::spawn_blocking(move || export::xlsx(&imanes, &columnas, &base_url)).await;
+            match libro {
+                Ok(Ok(bytes)) => axum::body::Body::from(bytes),
+                Ok(Err(e)) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Error armando el Excel: {}", e)).into_response(),
+                Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+            }
+        }
+    };
 
-    // 3. Devolver CSV
     (
         [
-            (header::CONTENT_TYPE, "text/csv"),
-            (header::CONTENT_DISPOSITION, "attachment; filename=\"lote_produccion_nuevo.csv\""),
+            (header::CONTENT_TYPE, formato.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, export::content_disposition(&nombre_archivo)),
         ],
-        csv_content,
+        cuerpo,
     ).into_response()
 }
 
-#[derive(Deserialize)]
-pub struct ExportQuery {
-    ts: Option<i64>,
+// Filtro compartido por todas las exportaciones de un lote.
+// `tipo` es un EstadoUso (virgin, scanned, active, disabled) o "full" para todo el lote.
+// Un tipo desconocido es un 400: mejor eso que entregar el lote entero por un typo
+pub(crate) fn filtro_lote(lote_nombre: &str, tipo: &str, ts: Option<i64>) -> Result<mongodb::bson::Document, (StatusCode, &'static str)> {
+    let mut filter = match (tipo, EstadoUso::parse(tipo)) {
+        ("full", _) => doc! {},
+        (_, Some(uso)) => uso.filtro(),
+        (_, None) => return Err((StatusCode::BAD_REQUEST, "❌ Tipo no soportado. Usa full, virgin, scanned, active o disabled.")),
+    };
+    filter.insert("lote_nombre", lote_nombre);
+
+    // Si nos pasan un timestamp, filtramos por el lote exacto
+    if let Some(ts) = ts {
+        filter.insert("lote_fecha", mongodb::bson::DateTime::from_millis(ts));
+    }
+
+    Ok(filter)
 }
 
-// GET /api/csv/:lote_nombre/:tipo
-pub async fn export_csv_lote(
+// GET /api/export/:lote_nombre/:tipo?formato=csv|xlsx|json|ndjson
+pub async fn export_lote(
     cookies: Cookies,
     State(state): State<AppState>,
     Path((lote_nombre, tipo)): Path<(String, String)>,
@@ -484,36 +1096,19 @@ pub async fn export_csv_lote(
     }
 
     let collection = state.db.collection::<Iman>("imanes");
-    
-    let mut filter = if tipo == "available" {
-        doc! { "lote_nombre": &lote_nombre, "visitas": 0 }
-    } else {
-        doc! { "lote_nombre": &lote_nombre }
+    let filter = match filtro_lote(&lote_nombre, &tipo, query.ts) {
+        Ok(f) => f,
+        Err(error) => return error.into_response(),
     };
 
-    // Si nos pasan un timestamp, filtramos por el lote exacto
-    if let Some(ts) = query.ts {
-        filter.insert("lote_fecha", mongodb::bson::DateTime::from_millis(ts));
-    }
-
     let options = mongodb::options::FindOptions::builder()
-        .sort(doc! { "codigo": 1 }) // Ordenar por código para que el CSV sea legible
+        .sort(doc! { "codigo": 1 }) // Ordenar por código para que el archivo sea legible
         .build();
 
-    let mut cursor = collection.find(filter, options).await.unwrap();
-    let mut csv_content = String::from("codigo,url_completa\n");
-    let base_url = std::env::var("BASE_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
-
-    while let Ok(Some(iman)) = cursor.try_next().await {
-        let linea = format!("{},{}/v/{}\n", iman.codigo, base_url, iman.codigo);
-        csv_content.push_str(&linea);
-    }
+    let cursor = match collection.find(filter, options).await {
+        Ok(c) => c,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response(),
+    };
 
-    (
-        [
-            (header::CONTENT_TYPE, "text/csv".to_string()),
-            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"lote_{}_{}.csv\"", lote_nombre, tipo)),
-        ],
-        csv_content,
-    ).into_response()
+    export_response(cursor, &query, &format!("lote_{}_{}", lote_nombre, tipo)).await
 }
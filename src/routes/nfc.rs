@@ -0,0 +1,98 @@
+// src/routes/nfc.rs
+// Exportaciones pensadas para grabar los tags directo desde el lote.
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+};
+use futures::stream::{StreamExt, TryStreamExt};
+use mongodb::bson::doc;
+use tower_cookies::Cookies;
+
+use super::{filtro_lote, ExportQuery};
+use crate::{export, models::Iman, nfc, AppState};
+
+// GET /api/nfc/:lote_nombre/:tipo/:formato
+//   ndef     -> ZIP con un CODIGO.ndef (mensaje NDEF crudo, prefijo https:// = 0x04)
+//   txt      -> Lista de URLs, una por línea (modo lote de NFC Tools y similares)
+//   csv      -> CSV para codificadoras masivas: secuencia, codigo, url, ndef_hex
+pub async fn export_nfc_lote(
+    cookies: Cookies,
+    State(state): State<AppState>,
+    Path((lote_nombre, tipo, formato)): Path<(String, String, String)>,
+    axum::extract::Query(query): axum::extract::Query<ExportQuery>,
+) -> Response {
+    if cookies.get("admin_session").is_none() {
+        return Redirect::to("/login").into_response();
+    }
+
+    let filter = match filtro_lote(&lote_nombre, &tipo, query.ts) {
+        Ok(f) => f,
+        Err(error) => return error.into_response(),
+    };
+    let options = mongodb::options::FindOptions::builder()
+        .sort(doc! { "codigo": 1 })
+        .build();
+
+    let cursor = match state.db.collection::<Iman>("imanes").find(filter, options).await {
+        Ok(c) => c,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response(),
+    };
+    let base_url = std::env::var("BASE_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
+
+    match formato.as_str() {
+        "csv" => {
+            // Columnas fijas: las codificadoras esperan siempre el mismo orden
+            let columnas = vec![export::Columna::Secuencia, export::Columna::Codigo, export::Columna::Url, export::Columna::NdefHex];
+            (
+                [
+                    (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+                    (header::CONTENT_DISPOSITION, export::content_disposition(&format!("codificadora_{}_{}.csv", lote_nombre, tipo))),
+                ],
+                axum::body::Body::from_stream(export::stream_csv(cursor, columnas, base_url, false)),
+            ).into_response()
+        }
+        "ndef" => {
+            // El ZIP necesita saber dónde termina cada archivo, así que este sí se arma en memoria
+            let imanes: Vec<Iman> = match cursor.try_collect().await {
+                Ok(v) => v,
+                Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response(),
+            };
+
+            let registros = imanes
+                .into_iter()
+                .map(|iman| {
+                    let url = format!("{}/v/{}", base_url, iman.codigo);
+                    (iman.codigo, url)
+                })
+                .collect();
+
+            // Comprimir es CPU pura: fuera del runtime async (como el Excel y los PDF)
+            match tokio::task::spawn_blocking(move || nfc::zip_ndef(registros)).await {
+                Ok(Ok(zip)) => (
+                    [
+                        (header::CONTENT_TYPE, "application/zip".to_string()),
+                        (header::CONTENT_DISPOSITION, export::content_disposition(&format!("ndef_{}_{}.zip", lote_nombre, tipo))),
+                    ],
+                    zip,
+                ).into_response(),
+                Ok(Err(e)) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Error armando ZIP: {}", e)).into_response(),
+                Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Error armando ZIP: {}", e)).into_response(),
+            }
+        }
+        "txt" => {
+            let lineas = cursor
+                .map_ok(move |iman| axum::body::Bytes::from(format!("{}/v/{}\n", base_url, iman.codigo)))
+                .map(|r| r.map_err(std::io::Error::other));
+
+            (
+                [
+                    (header::CONTENT_TYPE, "text/plain; charset=utf-8".to_string()),
+                    (header::CONTENT_DISPOSITION, export::content_disposition(&format!("urls_{}_{}.txt", lote_nombre, tipo))),
+                ],
+                axum::body::Body::from_stream(lineas),
+            ).into_response()
+        }
+        _ => (StatusCode::BAD_REQUEST, "❌ Formato no soportado (usa ndef, txt o csv)").into_response(),
+    }
+}
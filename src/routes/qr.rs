@@ -0,0 +1,233 @@
+// src/routes/qr.rs
+// QR individuales y hojas de stickers QR por lote.
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+};
+use futures::stream::TryStreamExt;
+use mongodb::bson::{doc, Document};
+use serde::Deserialize;
+use tower_cookies::Cookies;
+
+use super::{filtro_lote, ExportQuery};
+use crate::{codes, export, hojas, models::Iman, pin, qr, AppState};
+
+#[derive(Deserialize)]
+pub struct QrQuery {
+    // svg (default) o png
+    formato: Option<String>,
+    // Tamaño mínimo en pixeles
+    tam: Option<u32>,
+}
+
+// GET /api/admin/qr/:codigo
+pub async fn qr_iman(
+    cookies: Cookies,
+    Path(codigo): Path<String>,
+    axum::extract::Query(query): axum::extract::Query<QrQuery>,
+) -> Response {
+    if cookies.get("admin_session").is_none() {
+        return Redirect::to("/login").into_response();
+    }
+
+    if !codes::es_valido(&codigo) {
+        return (StatusCode::BAD_REQUEST, "❌ Código mal formado").into_response();
+    }
+
+    let base_url = std::env::var("BASE_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
+    let url = format!("{}/v/{}", base_url, codigo);
+    let tam = query.tam.unwrap_or(300).clamp(64, 2048);
+
+    match query.formato.as_deref() {
+        Some("png") => match qr::png(&url, tam) {
+            Ok(png) => ([(header::CONTENT_TYPE, "image/png")], png).into_response(),
+            Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Error generando QR: {}", e)).into_response(),
+        },
+        None | Some("svg") => match qr::svg(&url, tam) {
+            Ok(svg) => ([(header::CONTENT_TYPE, "image/svg+xml")], svg).into_response(),
+            Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Error generando QR: {}", e)).into_response(),
+        },
+        Some(_) => (StatusCode::BAD_REQUEST, "❌ Formato no soportado (usa svg o png)").into_response(),
+    }
+}
+
+// Imanes del lote según el mismo filtro que el CSV (ver filtro_lote)
+async fn imanes_lote(state: &AppState, filter: Document) -> Result<Vec<Iman>, mongodb::error::Error> {
+    let options = mongodb::options::FindOptions::builder()
+        .sort(doc! { "codigo": 1 })
+        .build();
+
+    state.db.collection::<Iman>("imanes").find(filter, options).await?.try_collect().await
+}
+
+// Arma el PDF fuera del runtime async (es CPU puro) y lo manda como descarga
+async fn pdf_response(titulo: String, plantilla: &'static hojas::Plantilla, etiquetas: Vec<hojas::Etiqueta>, nombre_archivo: &str) -> Response {
+    let pdf = tokio::task::spawn_blocking(move || hojas::pdf(&titulo, plantilla, &etiquetas)).await;
+
+    match pdf {
+        Ok(Ok(pdf)) => (
+            [
+                (header::CONTENT_TYPE, "application/pdf".to_string()),
+                (header::CONTENT_DISPOSITION, export::content_disposition(nombre_archivo)),
+            ],
+            pdf,
+        ).into_response(),
+        Ok(Err(e)) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Error generando PDF: {}", e)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Error generando PDF: {}", e)).into_response(),
+    }
+}
+
+// GET /api/hoja/:lote_nombre/:tipo
+// PDF A4 con un QR por imán, su código abajo y marcas de corte.
+pub async fn hoja_qr_lote(
+    cookies: Cookies,
+    State(state): State<AppState>,
+    Path((lote_nombre, tipo)): Path<(String, String)>,
+    axum::extract::Query(query): axum::extract::Query<ExportQuery>,
+) -> Response {
+    if cookies.get("admin_session").is_none() {
+        return Redirect::to("/login").into_response();
+    }
+
+    let filter = match filtro_lote(&lote_nombre, &tipo, query.ts) {
+        Ok(f) => f,
+        Err(error) => return error.into_response(),
+    };
+    let imanes = match imanes_lote(&state, filter).await {
+        Ok(v) => v,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response(),
+    };
+
+    if imanes.is_empty() {
+        return (StatusCode::OK, "⚠️ Este lote no tiene imanes con ese filtro.").into_response();
+    }
+
+    let base_url = std::env::var("BASE_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
+    let etiquetas = imanes
+        .iter()
+        .map(|iman| hojas::Etiqueta {
+            url: format!("{}/v/{}", base_url, iman.codigo),
+            lineas: vec![iman.codigo.clone()],
+        })
+        .collect();
+
+    pdf_response(
+        format!("QR {}", lote_nombre),
+        &hojas::QR_A4,
+        etiquetas,
+        &format!("qr_{}_{}.pdf", lote_nombre, tipo),
+    ).await
+}
+
+#[derive(Deserialize)]
+pub struct EtiquetasQuery {
+    ts: Option<i64>,
+    // Clave de hojas::PLANTILLAS (default: Avery L7160)
+    plantilla: Option<String>,
+}
+
+// PIN que va impreso: el de fábrica (se recalcula, en la base solo está su hash)
+// o uno viejo en texto plano. Los que se dieron al reclamar no se pueden imprimir.
+fn pin_impreso(iman: &Iman, secreto: Option<&str>) -> Option<String> {
+    match (&iman.owner_pin, secreto) {
+        (Some(pin), _) => Some(pin.clone()),
+        (None, Some(secreto)) if iman.pin_de_fabrica && iman.owner_pin_hash.is_some() => {
+            Some(pin::de_fabrica(secreto, &iman.codigo))
+        }
+        _ => None,
+    }
+}
+
+fn etiqueta_empaque(iman: &Iman, base_url: &str, secreto: Option<&str>) -> hojas::Etiqueta {
+    let url = format!("{}/v/{}", base_url, iman.codigo);
+    let mut lineas = vec![iman.codigo.clone(), url.clone()];
+    if let Some(pin) = pin_impreso(iman, secreto) {
+        lineas.push(format!("PIN: {}", pin));
+    }
+    hojas::Etiqueta { url, lineas }
+}
+
+// GET /api/etiquetas/:lote_nombre/:tipo
+// Etiquetas de empaque: código, URL, QR y PIN del propietario (si existe),
+// en la hoja precortada que elija producción. Un solo PDF por lote.
+pub async fn etiquetas_lote(
+    cookies: Cookies,
+    State(state): State<AppState>,
+    Path((lote_nombre, tipo)): Path<(String, String)>,
+    axum::extract::Query(query): axum::extract::Query<EtiquetasQuery>,
+) -> Response {
+    if cookies.get("admin_session").is_none() {
+        return Redirect::to("/login").into_response();
+    }
+
+    let clave = query.plantilla.as_deref().unwrap_or("l7160");
+    let Some(plantilla) = hojas::plantilla(clave) else {
+        return (StatusCode::BAD_REQUEST, format!("❌ Plantilla desconocida: {}", clave)).into_response();
+    };
+
+    let filter = match filtro_lote(&lote_nombre, &tipo, query.ts) {
+        Ok(f) => f,
+        Err(error) => return error.into_response(),
+    };
+    let imanes = match imanes_lote(&state, filter).await {
+        Ok(v) => v,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response(),
+    };
+
+    if imanes.is_empty() {
+        return (StatusCode::OK, "⚠️ Este lote no tiene imanes con ese filtro.").into_response();
+    }
+
+    let base_url = std::env::var("BASE_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
+    let secreto = pin::secreto();
+    let etiquetas = imanes.iter().map(|iman| etiqueta_empaque(iman, &base_url, secreto.as_deref())).collect();
+
+    pdf_response(
+        format!("Etiquetas {}", lote_nombre),
+        plantilla,
+        etiquetas,
+        &format!("etiquetas_{}_{}_{}.pdf", lote_nombre, tipo, plantilla.clave),
+    ).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn iman_de_fabrica(secreto: &str) -> Iman {
+        let codigo = "HUASTECA-748291-0001-E".to_string();
+        let hash = pin::hash(&pin::de_fabrica(secreto, &codigo));
+        Iman { codigo, owner_pin_hash: Some(hash), pin_de_fabrica: true, ..Default::default() }
+    }
+
+    #[test]
+    fn etiqueta_con_pin_de_fabrica() {
+        let iman = iman_de_fabrica("secreto");
+        let etiqueta = etiqueta_empaque(&iman, "https://imanes.mx", Some("secreto"));
+
+        let pin = pin::de_fabrica("secreto", &iman.codigo);
+        assert_eq!(etiqueta.lineas, vec![
+            "HUASTECA-748291-0001-E".to_string(),
+            "https://imanes.mx/v/HUASTECA-748291-0001-E".to_string(),
+            format!("PIN: {}", pin),
+        ]);
+        // Lo impreso es lo que acepta el panel del dueño
+        assert!(pin::coincide(&pin, iman.owner_pin_hash.as_deref().unwrap()));
+
+        // Y la hoja se arma con esa etiqueta
+        let plantilla = hojas::plantilla("l7160").unwrap();
+        assert!(hojas::pdf("Etiquetas", plantilla, &[etiqueta]).is_ok());
+    }
+
+    #[test]
+    fn etiqueta_sin_pin_de_fabrica() {
+        // PIN dado al reclamar: solo existe el hash, no hay nada que imprimir
+        let iman = Iman { codigo: "HUASTECA-748291-0002-C".to_string(), owner_pin_hash: Some(pin::hash("123456")), ..Default::default() };
+        assert_eq!(etiqueta_empaque(&iman, "https://imanes.mx", Some("secreto")).lineas.len(), 2);
+
+        // Sin PIN_SECRETO tampoco
+        let iman = iman_de_fabrica("secreto");
+        assert_eq!(etiqueta_empaque(&iman, "https://imanes.mx", None).lineas.len(), 2);
+    }
+}
@@ -0,0 +1,413 @@
+// src/routes/reportes.rs
+// Reportes de abuso: quien escanea un imán ajeno puede avisar que el destino es
+// malicioso. Con suficientes reportes el imán se pausa solo hasta que un admin lo revise.
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, Form, Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use futures::stream::TryStreamExt;
+use hmac::{Hmac, Mac};
+use mongodb::bson::{doc, DateTime};
+use serde::Deserialize;
+use sha2::Sha256;
+use tower_cookies::Cookies;
+
+use crate::{
+    codes, db, destinos, estados,
+    models::{AlertaSeguridad, EstadoIman, EstadoReporte, Iman, Reporte},
+    AppState,
+};
+
+/// Motivos que se pueden elegir (clave, etiqueta)
+pub const MOTIVOS: [(&str, &str); 5] = [
+    ("phishing", "Suplanta a otra persona o marca / pide contraseñas"),
+    ("malware", "Descarga algo raro o un virus"),
+    ("spam", "Publicidad o spam"),
+    ("inapropiado", "Contenido inapropiado u ofensivo"),
+    ("otro", "Otra cosa"),
+];
+
+const MAX_COMENTARIO: usize = 500;
+
+// Motivo con el que la auto-pausa queda en el historial (para saber si la deshacemos al descartar)
+const MOTIVO_PAUSA: &str = "reportes";
+
+#[derive(Deserialize)]
+pub struct ReportarQuery {
+    codigo: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ReporteForm {
+    codigo: String,
+    motivo: String,
+    #[serde(default)]
+    comentario: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResolverForm {
+    // "descartar" o "bloquear"
+    accion: String,
+}
+
+// Reportes pendientes con los que un imán activo se pausa solo (0 = nunca)
+fn umbral_pausa() -> u64 {
+    std::env::var("REPORTES_PARA_PAUSAR")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(3)
+}
+
+// Reportes que una misma IP puede mandar por hora (entre todos los imanes)
+fn limite_por_hora() -> u64 {
+    std::env::var("REPORTES_POR_HORA")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(5)
+}
+
+// IP real de quien reporta. X-Forwarded-For lo escribe el cliente como quiera: solo nos
+// fiamos de lo que agregaron nuestros proxies, que van al final. Con PROXIES_CONFIABLES=N
+// tomamos la N-ésima entrada contando desde la derecha; sin proxies (0), la conexión TCP.
+fn ip_cliente(headers: &HeaderMap, conexion: SocketAddr) -> String {
+    let proxies: usize = std::env::var("PROXIES_CONFIABLES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    if proxies == 0 {
+        return conexion.ip().to_string();
+    }
+
+    let saltos: Vec<&str> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect();
+    match saltos.len().checked_sub(proxies) {
+        Some(i) => saltos[i].to_string(),
+        // Llegó con menos saltos de los esperados: alguien le habló directo al servidor
+        None => conexion.ip().to_string(),
+    }
+}
+
+// HMAC-SHA256 con un secreto del servidor: estable entre versiones de Rust (el índice único
+// depende de eso) y sin el secreto no se puede sacar la IP probando todas. No guardamos IPs.
+fn huella(partes: &[&str]) -> String {
+    let secreto = std::env::var("HUELLA_SECRETO")
+        .or_else(|_| std::env::var("ADMIN_PASSWORD"))
+        .unwrap_or_else(|_| "admin123".to_string());
+    let mut mac = Hmac::<Sha256>::new_from_slice(secreto.as_bytes()).expect("HMAC acepta llaves de cualquier largo");
+    for parte in partes {
+        mac.update(parte.as_bytes());
+        // Separador: que ("ab", "c") y ("a", "bc") no den lo mismo
+        mac.update(&[0]);
+    }
+    hex::encode(mac.finalize().into_bytes())
+}
+
+fn render_reportar(state: &AppState, mut context: tera::Context, status: StatusCode) -> Response {
+    let motivos: Vec<serde_json::Value> = MOTIVOS
+        .iter()
+        .map(|(clave, etiqueta)| serde_json::json!({ "clave": clave, "etiqueta": etiqueta }))
+        .collect();
+    context.insert("motivos", &motivos);
+
+    match state.tera.render("reportar.html", &context) {
+        Ok(html) => (status, Html(html)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Template error: {}", e)).into_response(),
+    }
+}
+
+// GET /reportar?codigo=...
+pub async fn reportar_page(
+    State(state): State<AppState>,
+    Query(query): Query<ReportarQuery>,
+) -> Response {
+    let mut context = tera::Context::new();
+    context.insert("codigo", &query.codigo.unwrap_or_default());
+    render_reportar(&state, context, StatusCode::OK)
+}
+
+// POST /api/reportar
+pub async fn reportar(
+    State(state): State<AppState>,
+    ConnectInfo(conexion): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Form(form): Form<ReporteForm>,
+) -> Response {
+    // Tal cual, igual que en /v/:codigo (hay códigos legacy en minúsculas)
+    let codigo = form.codigo.trim().to_string();
+    let comentario = form.comentario.trim();
+
+    let mut context = tera::Context::new();
+    context.insert("codigo", &codigo);
+    context.insert("comentario", comentario);
+
+    let error = if !codes::es_valido(&codigo) {
+        Some("Revisa el código: viene impreso en el imán o en la dirección (después de /v/).")
+    } else if !MOTIVOS.iter().any(|(clave, _)| *clave == form.motivo) {
+        Some("Elige un motivo.")
+    } else if comentario.chars().count() > MAX_COMENTARIO {
+        Some("El comentario es muy largo (máximo 500 caracteres).")
+    } else {
+        None
+    };
+    if let Some(error) = error {
+        context.insert("error", error);
+        return render_reportar(&state, context, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    let imanes = state.db.collection::<Iman>("imanes");
+    let iman = match imanes.find_one(doc! { "codigo": &codigo }, None).await {
+        Ok(Some(i)) => i,
+        Ok(None) => {
+            context.insert("error", "Ese código no existe. Revisa que esté bien escrito.");
+            return render_reportar(&state, context, StatusCode::NOT_FOUND);
+        }
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response(),
+    };
+
+    // Límite por IP antes de guardar nada: así unas cuantas peticiones no bastan para pausar imanes ajenos
+    let ip = ip_cliente(&headers, conexion);
+    let red = huella(&[&ip]);
+    let reportes = state.db.collection::<Reporte>("reportes");
+    let hace_una_hora = DateTime::from_millis(DateTime::now().timestamp_millis() - 60 * 60 * 1000);
+    match reportes.count_documents(doc! { "red": &red, "at": { "$gte": hace_una_hora } }, None).await {
+        Ok(n) if n >= limite_por_hora() => {
+            context.insert("error", "Ya recibimos varios reportes tuyos. Intenta de nuevo más tarde.");
+            return render_reportar(&state, context, StatusCode::TOO_MANY_REQUESTS);
+        }
+        Ok(_) => {}
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response(),
+    }
+
+    let reporte = Reporte {
+        id: None,
+        codigo: codigo.clone(),
+        motivo: form.motivo.clone(),
+        comentario: Some(comentario.to_string()).filter(|c| !c.is_empty()),
+        url: destinos::url_vigente(&iman, DateTime::now()).map(str::to_string),
+        huella: huella(&[&ip, &codigo]),
+        red,
+        estado: EstadoReporte::Pendiente,
+        at: DateTime::now(),
+    };
+
+    match reportes.insert_one(&reporte, None).await {
+        Ok(_) => {}
+        // Esta persona ya tiene un reporte pendiente de este imán: le damos las gracias igual,
+        // pero no cuenta doble (ya resuelto, un reporte nuevo sí entra)
+        Err(e) if db::es_clave_duplicada(&e) => {
+            context.insert("enviado", &true);
+            return render_reportar(&state, context, StatusCode::OK);
+        }
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response(),
+    }
+
+    // ¿Ya son suficientes para pausarlo mientras lo revisamos?
+    let umbral = umbral_pausa();
+    let filtro = doc! { "codigo": &codigo, "estado": "pendiente" };
+    if umbral > 0 && iman.estado == EstadoIman::Claimed {
+        match reportes.count_documents(filtro, None).await {
+            Ok(n) if n >= umbral => {
+                let alerta = AlertaSeguridad {
+                    url: reporte.url.clone().unwrap_or_default(),
+                    motivo: format!("{} reportes de abuso", n),
+                    at: DateTime::now(),
+                };
+                if let Ok(alerta) = mongodb::bson::to_bson(&alerta) {
+                    let resultado = estados::transicionar(
+                        &state.db,
+                        &codigo,
+                        EstadoIman::Paused,
+                        Some(&[EstadoIman::Claimed]),
+                        Some(MOTIVO_PAUSA),
+                        doc! { "alerta_seguridad": alerta },
+                    )
+                    .await;
+                    match resultado {
+                        Ok(_) => println!("🚩 {} pausado por {} reportes", codigo, n),
+                        Err(e) => eprintln!("❌ No se pudo pausar {} por reportes: {}", codigo, e),
+                    }
+                }
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("❌ Error contando reportes de {}: {}", codigo, e),
+        }
+    }
+
+    context.insert("enviado", &true);
+    render_reportar(&state, context, StatusCode::OK)
+}
+
+// GET /admin/reportes
+// Cola de moderación: reportes pendientes agrupados por imán (el más reportado primero)
+pub async fn reportes_page(
+    cookies: Cookies,
+    State(state): State<AppState>,
+) -> Response {
+    if cookies.get("admin_session").is_none() {
+        return Redirect::to("/login").into_response();
+    }
+
+    render_reportes(&state, tera::Context::new()).await
+}
+
+async fn render_reportes(state: &AppState, extra: tera::Context) -> Response {
+    let options = mongodb::options::FindOptions::builder().sort(doc! { "at": -1 }).limit(2000).build();
+    let pendientes: Vec<Reporte> = match state
+        .db
+        .collection::<Reporte>("reportes")
+        .find(doc! { "estado": "pendiente" }, options)
+        .await
+    {
+        Ok(cursor) => match cursor.try_collect().await {
+            Ok(r) => r,
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response(),
+        },
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response(),
+    };
+
+    // Agrupamos por código conservando el orden (el reporte más reciente define la posición)
+    let mut orden: Vec<String> = Vec::new();
+    let mut grupos: HashMap<String, Vec<Reporte>> = HashMap::new();
+    for reporte in pendientes {
+        if !grupos.contains_key(&reporte.codigo) {
+            orden.push(reporte.codigo.clone());
+        }
+        grupos.entry(reporte.codigo.clone()).or_default().push(reporte);
+    }
+
+    let estados_actuales: HashMap<String, EstadoIman> = match state
+        .db
+        .collection::<Iman>("imanes")
+        .find(doc! { "codigo": { "$in": &orden } }, None)
+        .await
+    {
+        Ok(cursor) => cursor
+            .try_collect::<Vec<Iman>>()
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .map(|i| (i.codigo, i.estado))
+            .collect(),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response(),
+    };
+
+    let fecha_iso = |dt: DateTime| chrono::DateTime::from_timestamp_millis(dt.timestamp_millis()).map(|d| d.to_rfc3339());
+    let mut cola: Vec<serde_json::Value> = orden
+        .into_iter()
+        .map(|codigo| {
+            let reportes = &grupos[&codigo];
+            let mut por_motivo: Vec<(String, usize)> = MOTIVOS
+                .iter()
+                .map(|(clave, _)| (clave.to_string(), reportes.iter().filter(|r| r.motivo == *clave).count()))
+                .filter(|(_, n)| *n > 0)
+                .collect();
+            por_motivo.sort_by_key(|(_, n)| std::cmp::Reverse(*n));
+
+            let estado = estados_actuales.get(&codigo).copied();
+            serde_json::json!({
+                "codigo": codigo,
+                "total": reportes.len(),
+                "estado": estado.map(estados::etiqueta),
+                "url": reportes.iter().find_map(|r| r.url.clone()),
+                "por_motivo": por_motivo,
+                "comentarios": reportes
+                    .iter()
+                    .filter_map(|r| Some(serde_json::json!({ "texto": r.comentario.clone()?, "at": fecha_iso(r.at) })))
+                    .take(5)
+                    .collect::<Vec<_>>(),
+                "ultimo": fecha_iso(reportes[0].at),
+            })
+        })
+        .collect();
+    cola.sort_by_key(|g| std::cmp::Reverse(g["total"].as_u64().unwrap_or(0)));
+
+    let mut context = tera::Context::new();
+    context.insert("cola", &cola);
+    context.insert("umbral", &umbral_pausa());
+    context.extend(extra);
+
+    match state.tera.render("reportes.html", &context) {
+        Ok(html) => Html(html).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+    }
+}
+
+// POST /api/admin/reportes/:codigo/resolver
+pub async fn resolver_reportes(
+    cookies: Cookies,
+    State(state): State<AppState>,
+    Path(codigo): Path<String>,
+    Form(form): Form<ResolverForm>,
+) -> Response {
+    if cookies.get("admin_session").is_none() {
+        return Redirect::to("/login").into_response();
+    }
+
+    let (estado_reportes, mensaje) = match form.accion.as_str() {
+        "descartar" => (EstadoReporte::Descartado, format!("✅ Reportes de {} descartados.", codigo)),
+        "bloquear" => (EstadoReporte::Confirmado, format!("⛔ {} bloqueado.", codigo)),
+        _ => return (StatusCode::BAD_REQUEST, "❌ Acción no válida").into_response(),
+    };
+
+    let mut extra = tera::Context::new();
+    let imanes = state.db.collection::<Iman>("imanes");
+
+    if estado_reportes == EstadoReporte::Confirmado {
+        let resultado = estados::transicionar(&state.db, &codigo, EstadoIman::Disabled, None, Some("reportes confirmados"), doc! {}).await;
+        if let Err(e) = resultado {
+            extra.insert("error", &format!("{}: {}", codigo, e));
+            let mut response = render_reportes(&state, extra).await;
+            *response.status_mut() = StatusCode::CONFLICT;
+            return response;
+        }
+    } else {
+        // Falsa alarma: si lo pausaron los reportes, lo regresamos como estaba
+        match imanes.find_one(doc! { "codigo": &codigo }, None).await {
+            Ok(Some(iman)) => {
+                let pausado_por_reportes = iman.estado == EstadoIman::Paused
+                    && iman.historial.last().and_then(|c| c.motivo.as_deref()) == Some(MOTIVO_PAUSA);
+                if pausado_por_reportes {
+                    let update = doc! { "$unset": { "alerta_seguridad": "" } };
+                    if let Err(e) = imanes.update_one(doc! { "codigo": &codigo }, update, None).await {
+                        return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response();
+                    }
+                    let resultado = estados::transicionar(
+                        &state.db, &codigo, EstadoIman::Claimed, Some(&[EstadoIman::Paused]), Some("reportes descartados"), doc! {},
+                    )
+                    .await;
+                    if let Err(e) = resultado {
+                        eprintln!("❌ No se pudo reactivar {}: {}", codigo, e);
+                    }
+                }
+            }
+            Ok(None) => {}
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response(),
+        }
+    }
+
+    let Ok(estado_bson) = mongodb::bson::to_bson(&estado_reportes) else {
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Error serializando el estado").into_response();
+    };
+    let resultado = state
+        .db
+        .collection::<Reporte>("reportes")
+        .update_many(doc! { "codigo": &codigo, "estado": "pendiente" }, doc! { "$set": { "estado": estado_bson } }, None)
+        .await;
+    if let Err(e) = resultado {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response();
+    }
+
+    extra.insert("mensaje", &mensaje);
+    render_reportes(&state, extra).await
+}
@@ -0,0 +1,115 @@
+// src/routes/transiciones.rs
+// Cambios de estado manuales desde el panel (un imán o un lote completo).
+use axum::{
+    extract::{Form, Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+};
+use mongodb::bson::{doc, DateTime};
+use serde::Deserialize;
+use tower_cookies::Cookies;
+
+use super::render_admin;
+use crate::{estados, models::{EstadoIman, Iman}, AppState};
+
+#[derive(Deserialize)]
+pub struct CambioEstadoForm {
+    estado: EstadoIman,
+    motivo: Option<String>,
+    // Solo para lotes: el lote exacto (mismo `ts` que las exportaciones)
+    ts: Option<i64>,
+}
+
+fn motivo(form: &CambioEstadoForm) -> Option<&str> {
+    form.motivo.as_deref().map(str::trim).filter(|m| !m.is_empty())
+}
+
+// POST /api/admin/imanes/:codigo/estado
+pub async fn cambiar_estado_iman(
+    cookies: Cookies,
+    State(state): State<AppState>,
+    Path(codigo): Path<String>,
+    Form(form): Form<CambioEstadoForm>,
+) -> Response {
+    if cookies.get("admin_session").is_none() {
+        return Redirect::to("/login").into_response();
+    }
+
+    // Tal cual: los códigos legacy pueden venir en minúsculas
+    let codigo = codigo.trim();
+    let mut context = tera::Context::new();
+
+    // Activo sin destino = imán inservible (muestra el setup pero ya no se puede reclamar)
+    let extra = if form.estado == EstadoIman::Claimed {
+        let collection = state.db.collection::<Iman>("imanes");
+        match collection.find_one(doc! { "codigo": codigo }, None).await {
+            Ok(Some(iman)) if !iman.tiene_destino() => {
+                context.insert("error_estado", &format!("{}: no tiene destino configurado, no se puede activar", codigo));
+                let mut response = render_admin(&state, context).await;
+                *response.status_mut() = StatusCode::CONFLICT;
+                return response;
+            }
+            Ok(_) => {}
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response(),
+        }
+        doc! { "activated_at": DateTime::now() }
+    } else {
+        doc! {}
+    };
+
+    let resultado = estados::transicionar(&state.db, codigo, form.estado, None, motivo(&form), extra).await;
+    match resultado {
+        Ok(iman) => {
+            context.insert("mensaje_estado", &format!("✅ {} ahora está \"{}\"", iman.codigo, estados::etiqueta(iman.estado)));
+            render_admin(&state, context).await
+        }
+        Err(e) => {
+            context.insert("error_estado", &format!("{}: {}", codigo, e));
+            let mut response = render_admin(&state, context).await;
+            *response.status_mut() = StatusCode::CONFLICT;
+            response
+        }
+    }
+}
+
+// POST /api/admin/lotes/:lote_nombre/estado
+// Los que no puedan pasar al estado pedido (ej. ya reclamados al marcar "vendido") se quedan igual.
+pub async fn cambiar_estado_lote(
+    cookies: Cookies,
+    State(state): State<AppState>,
+    Path(lote_nombre): Path<String>,
+    Form(form): Form<CambioEstadoForm>,
+) -> Response {
+    if cookies.get("admin_session").is_none() {
+        return Redirect::to("/login").into_response();
+    }
+
+    // Activar en bloque dejaría imanes sin destino: eso solo se hace uno por uno (o con el import)
+    if form.estado == EstadoIman::Claimed {
+        let mut context = tera::Context::new();
+        context.insert("error_estado", "Un lote no se puede marcar como activo completo: cada imán necesita su destino.");
+        let mut response = render_admin(&state, context).await;
+        *response.status_mut() = StatusCode::UNPROCESSABLE_ENTITY;
+        return response;
+    }
+
+    let mut filter = estados::filtro_origen(form.estado);
+    filter.insert("lote_nombre", &lote_nombre);
+    if let Some(ts) = form.ts {
+        filter.insert("lote_fecha", DateTime::from_millis(ts));
+    }
+
+    let update = estados::update(form.estado, motivo(&form), doc! {});
+    let resultado = state.db.collection::<Iman>("imanes").update_many(filter, update, None).await;
+
+    let mut context = tera::Context::new();
+    match resultado {
+        Ok(r) => context.insert(
+            "mensaje_estado",
+            &format!("✅ {} imanes de \"{}\" pasaron a \"{}\"", r.modified_count, lote_nombre, estados::etiqueta(form.estado)),
+        ),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)).into_response(),
+    }
+
+    render_admin(&state, context).await
+}
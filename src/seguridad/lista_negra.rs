@@ -0,0 +1,351 @@
+// src/seguridad/lista_negra.rs
+// Verificador local: un archivo de texto que se puede editar sin recompilar.
+//
+// Formato (una regla por línea, # para comentarios):
+//   malo.com              -> bloquea malo.com y todos sus subdominios
+//   re:^https://[^/]+/login  -> regex contra la URL completa
+//   protegido:mibanco.com -> marca a cuidar de imitaciones (además de las redes de siempre)
+//   permitido:modern.com  -> dominio legítimo que se parece a una marca: no se revisa por imitación
+use std::collections::HashSet;
+use std::sync::RwLock;
+
+use regex::Regex;
+
+use super::{Veredicto, VerificadorUrl};
+
+// Marcas que siempre cuidamos de imitaciones (las mismas que permite validar_destino)
+const PROTEGIDOS_BASE: [&str; 8] = [
+    "instagram.com", "tiktok.com", "facebook.com", "youtube.com", "twitter.com", "x.com", "whatsapp.com", "wa.me",
+];
+
+#[derive(Default)]
+struct Reglas {
+    dominios: HashSet<String>,
+    patrones: Vec<Regex>,
+    protegidos: Vec<String>,
+    permitidos: Vec<String>,
+}
+
+pub struct ListaNegra {
+    ruta: String,
+    reglas: RwLock<Reglas>,
+}
+
+impl ListaNegra {
+    pub fn desde_archivo(ruta: String) -> Self {
+        let reglas = leer(&ruta);
+        ListaNegra { ruta, reglas: RwLock::new(reglas) }
+    }
+}
+
+fn leer(ruta: &str) -> Reglas {
+    let contenido = match std::fs::read_to_string(ruta) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("⚠️ No se pudo leer la lista negra {}: {} (solo se revisan imitaciones)", ruta, e);
+            String::new()
+        }
+    };
+
+    let reglas = parsear(&contenido);
+    println!(
+        "🛡️ Lista negra: {} dominios, {} regex, {} marcas protegidas, {} permitidos",
+        reglas.dominios.len(), reglas.patrones.len(), reglas.protegidos.len(), reglas.permitidos.len()
+    );
+    reglas
+}
+
+fn parsear(contenido: &str) -> Reglas {
+    let mut reglas = Reglas {
+        protegidos: PROTEGIDOS_BASE.iter().map(|d| d.to_string()).collect(),
+        ..Default::default()
+    };
+
+    for (i, linea) in contenido.lines().enumerate() {
+        let linea = linea.trim();
+        if linea.is_empty() || linea.starts_with('#') {
+            continue;
+        }
+        if let Some(patron) = linea.strip_prefix("re:") {
+            match Regex::new(patron.trim()) {
+                Ok(re) => reglas.patrones.push(re),
+                Err(e) => eprintln!("⚠️ Lista negra, línea {}: regex inválida: {}", i + 1, e),
+            }
+        } else if let Some(dominio) = linea.strip_prefix("protegido:") {
+            reglas.protegidos.push(normalizar_dominio(dominio));
+        } else if let Some(dominio) = linea.strip_prefix("permitido:") {
+            reglas.permitidos.push(normalizar_dominio(dominio));
+        } else {
+            reglas.dominios.insert(normalizar_dominio(linea));
+        }
+    }
+
+    reglas
+}
+
+// Minúsculas, sin punto final y en ASCII (punycode), igual que lo deja url::Url
+fn normalizar_dominio(dominio: &str) -> String {
+    let dominio = dominio.trim().trim_end_matches('.').to_lowercase();
+    idna::domain_to_ascii(&dominio).unwrap_or(dominio)
+}
+
+// ¿`host` es `dominio` o un subdominio suyo?
+fn es_o_subdominio(host: &str, dominio: &str) -> bool {
+    host == dominio || host.ends_with(&format!(".{}", dominio))
+}
+
+// Las últimas `n` etiquetas ("login.1nstagram.com", 2 -> "1nstagram.com")
+fn ultimas_etiquetas(dominio: &str, n: usize) -> Option<String> {
+    let etiquetas: Vec<&str> = dominio.split('.').collect();
+    let inicio = etiquetas.len().checked_sub(n)?;
+    Some(etiquetas[inicio..].join("."))
+}
+
+// Letras de otros alfabetos que se ven idénticas a letras latinas
+fn latina_parecida(c: char) -> char {
+    match c {
+        'а' | 'α' => 'a',
+        'в' => 'b',
+        'с' | 'ϲ' => 'c',
+        'ԁ' => 'd',
+        'е' | 'ε' => 'e',
+        'ɡ' => 'g',
+        'һ' | 'н' => 'h',
+        'і' | 'ι' | 'ı' | 'í' | 'ì' | 'ï' => 'i',
+        'ј' => 'j',
+        'к' | 'κ' => 'k',
+        'ӏ' => 'l',
+        'м' => 'm',
+        'о' | 'ο' | 'ó' | 'ò' | 'ö' => 'o',
+        'р' | 'ρ' => 'p',
+        'ѕ' => 's',
+        'т' | 'τ' => 't',
+        'ν' => 'v',
+        'ԝ' => 'w',
+        'х' | 'χ' => 'x',
+        'у' | 'γ' => 'y',
+        _ => c,
+    }
+}
+
+/// "Esqueleto" de un dominio: lo que un humano cree leer. Dos dominios con el mismo
+/// esqueleto se confunden a simple vista (instagram.com vs іnstagrаm.com vs 1nstagrarn.com).
+/// Solo se compara contra las marcas protegidas, etiqueta por etiqueta desde el final.
+fn esqueleto(dominio: &str) -> String {
+    dominio
+        .chars()
+        .map(latina_parecida)
+        .map(|c| match c {
+            '1' | 'i' | '|' => 'l',
+            '0' => 'o',
+            _ => c,
+        })
+        .collect::<String>()
+        .replace("rn", "m")
+        .replace("vv", "w")
+}
+
+#[derive(PartialEq)]
+enum Alfabeto {
+    Latino,
+    Cirilico,
+    Griego,
+    Otro,
+}
+
+fn alfabeto(c: char) -> Alfabeto {
+    match c as u32 {
+        0x41..=0x5A | 0x61..=0x7A | 0xC0..=0x24F => Alfabeto::Latino,
+        0x370..=0x3FF => Alfabeto::Griego,
+        0x400..=0x52F => Alfabeto::Cirilico,
+        _ => Alfabeto::Otro,
+    }
+}
+
+// Una etiqueta que mezcla latino con cirílico o griego casi siempre es una trampa
+fn mezcla_alfabetos(etiqueta: &str) -> bool {
+    let alfabetos: Vec<Alfabeto> = etiqueta.chars().map(alfabeto).filter(|a| *a != Alfabeto::Otro).collect();
+    alfabetos.contains(&Alfabeto::Latino)
+        && (alfabetos.contains(&Alfabeto::Cirilico) || alfabetos.contains(&Alfabeto::Griego))
+}
+
+#[async_trait::async_trait]
+impl VerificadorUrl for ListaNegra {
+    fn nombre(&self) -> &'static str {
+        "lista negra"
+    }
+
+    async fn revisar(&self, url: &url::Url) -> Veredicto {
+        let Some(host) = url.host_str() else {
+            return Veredicto::Bloqueado("la URL no tiene dominio".to_string());
+        };
+        let host = host.trim_end_matches('.').to_lowercase();
+        let reglas = self.reglas.read().unwrap_or_else(|e| e.into_inner());
+
+        // 1. Dominios bloqueados (y sus subdominios)
+        if let Some(dominio) = reglas.dominios.iter().find(|d| es_o_subdominio(&host, d)) {
+            return Veredicto::Bloqueado(format!("dominio bloqueado ({})", dominio));
+        }
+
+        // 2. Reglas regex sobre la URL completa
+        if let Some(re) = reglas.patrones.iter().find(|re| re.is_match(url.as_str())) {
+            return Veredicto::Bloqueado(format!("coincide con la regla {}", re.as_str()));
+        }
+
+        // Lo que sigue son heurísticas: un dominio legítimo que caiga en ellas se exenta con "permitido:"
+        if reglas.permitidos.iter().any(|p| es_o_subdominio(&host, p)) {
+            return Veredicto::Seguro;
+        }
+
+        // 3. Homoglifos: url::Url deja los dominios internacionales en punycode (xn--)
+        let (unicode, resultado) = idna::domain_to_unicode(&host);
+        if host.split('.').any(|e| e.starts_with("xn--")) {
+            if resultado.is_err() {
+                return Veredicto::Bloqueado("dominio punycode mal formado".to_string());
+            }
+            if let Some(etiqueta) = unicode.split('.').find(|e| mezcla_alfabetos(e)) {
+                return Veredicto::Bloqueado(format!("\"{}\" mezcla letras de distintos alfabetos", etiqueta));
+            }
+        }
+
+        // 4. Imitaciones de marcas protegidas (también en ASCII: 1nstagram.com, instagrarn.com).
+        // Solo las últimas etiquetas, tantas como tenga la marca: el resto del host
+        // (subdominios) no se toca, así "rnail.algo.com" no se vuelve "mail"
+        for protegido in &reglas.protegidos {
+            let Some(cola) = ultimas_etiquetas(&unicode, protegido.split('.').count()) else {
+                continue;
+            };
+            if esqueleto(&cola) == esqueleto(protegido) && !es_o_subdominio(&host, protegido) {
+                return Veredicto::Bloqueado(format!("{} imita a {}", unicode, protegido));
+            }
+        }
+
+        Veredicto::Seguro
+    }
+
+    async fn recargar(&self) {
+        let nuevas = leer(&self.ruta);
+        *self.reglas.write().unwrap_or_else(|e| e.into_inner()) = nuevas;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn con_reglas(contenido: &str) -> ListaNegra {
+        ListaNegra { ruta: String::new(), reglas: RwLock::new(parsear(contenido)) }
+    }
+
+    async fn veredicto(lista: &ListaNegra, url: &str) -> Veredicto {
+        lista.revisar(&url::Url::parse(url).unwrap()).await
+    }
+
+    fn bloqueado(v: &Veredicto) -> bool {
+        matches!(v, Veredicto::Bloqueado(_))
+    }
+
+    #[tokio::test]
+    async fn bloquea_dominio_y_subdominios() {
+        let lista = con_reglas("bit.ly\n");
+        assert!(bloqueado(&veredicto(&lista, "https://bit.ly/abc").await));
+        assert!(bloqueado(&veredicto(&lista, "https://www.bit.ly/abc").await));
+        assert_eq!(veredicto(&lista, "https://notbit.ly/abc").await, Veredicto::Seguro);
+    }
+
+    #[tokio::test]
+    async fn regex_contra_la_url_completa() {
+        let lista = con_reglas("re:/wp-login\\.php\n");
+        assert!(bloqueado(&veredicto(&lista, "https://algo.com/wp-login.php").await));
+        assert_eq!(veredicto(&lista, "https://algo.com/").await, Veredicto::Seguro);
+    }
+
+    #[tokio::test]
+    async fn subdominios_legitimos_de_marcas() {
+        let lista = con_reglas("");
+        for url in [
+            "https://instagram.com/usuario",
+            "https://www.instagram.com/usuario",
+            "https://m.youtube.com/watch?v=1",
+            "https://vm.tiktok.com/abc",
+            "https://wa.me/5215512345678",
+        ] {
+            assert_eq!(veredicto(&lista, url).await, Veredicto::Seguro, "{}", url);
+        }
+    }
+
+    #[tokio::test]
+    async fn imitaciones_ascii() {
+        let lista = con_reglas("");
+        for url in [
+            "https://1nstagram.com/login",
+            "https://instagrarn.com/login",
+            "https://lnstagram.com/",
+            "https://login.1nstagram.com/",
+            "https://faceb0ok.com/",
+            "https://vva.me/123",
+        ] {
+            assert!(bloqueado(&veredicto(&lista, url).await), "{}", url);
+        }
+    }
+
+    #[tokio::test]
+    async fn cirilico_mezclado_en_punycode() {
+        let lista = con_reglas("");
+        // "а" cirílica: url::Url lo pasa a xn--...
+        let url = url::Url::parse("https://instаgram.com/").unwrap();
+        assert!(url.host_str().unwrap().starts_with("xn--"));
+        assert!(bloqueado(&lista.revisar(&url).await));
+
+        // Mezcla de alfabetos aunque no imite a ninguna marca
+        assert!(bloqueado(&veredicto(&lista, "https://pаypal-ejemplo.com/").await));
+    }
+
+    #[tokio::test]
+    async fn dominio_solo_cirilico_no_es_sospechoso() {
+        let lista = con_reglas("");
+        assert_eq!(veredicto(&lista, "https://пример.рф/").await, Veredicto::Seguro);
+    }
+
+    #[tokio::test]
+    async fn mismo_esqueleto_sin_marca_protegida() {
+        // modern.com y modem.com se ven iguales, pero ninguno es una marca protegida
+        let lista = con_reglas("");
+        assert_eq!(veredicto(&lista, "https://modern.com/").await, Veredicto::Seguro);
+        assert_eq!(veredicto(&lista, "https://modem.com/").await, Veredicto::Seguro);
+    }
+
+    #[tokio::test]
+    async fn subdominio_parecido_no_cuenta() {
+        // "rnail" se lee "mail", pero solo se comparan las etiquetas de la marca
+        let lista = con_reglas("protegido:mail.com\n");
+        assert_eq!(veredicto(&lista, "https://rnail.ejemplo.com/").await, Veredicto::Seguro);
+        assert!(bloqueado(&veredicto(&lista, "https://rnail.com/").await));
+    }
+
+    #[tokio::test]
+    async fn permitido_exenta_de_imitaciones() {
+        let lista = con_reglas("protegido:modem.com\n");
+        assert!(bloqueado(&veredicto(&lista, "https://modern.com/").await));
+
+        let lista = con_reglas("protegido:modem.com\npermitido:modern.com\n");
+        assert_eq!(veredicto(&lista, "https://modern.com/").await, Veredicto::Seguro);
+        assert_eq!(veredicto(&lista, "https://www.modern.com/").await, Veredicto::Seguro);
+    }
+
+    #[test]
+    fn esqueleto_de_homoglifos() {
+        assert_eq!(esqueleto("1nstagrarn.com"), esqueleto("instagram.com"));
+        assert_eq!(esqueleto("іnstagrаm.com"), esqueleto("instagram.com"));
+        assert_ne!(esqueleto("instagram.net"), esqueleto("instagram.com"));
+    }
+
+    #[test]
+    fn mezcla_de_alfabetos() {
+        assert!(mezcla_alfabetos("instаgram"));
+        assert!(mezcla_alfabetos("fαcebook"));
+        assert!(!mezcla_alfabetos("instagram"));
+        assert!(!mezcla_alfabetos("пример"));
+        assert!(!mezcla_alfabetos("peña"));
+    }
+}
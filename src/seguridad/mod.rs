@@ -0,0 +1,103 @@
+// src/seguridad/mod.rs
+// ¿Es seguro el destino de un imán? La lista de dominios permitidos de
+// destinos::validar_destino no basta: un link de instagram.com puede ser legítimo
+// hoy y un dominio parecido (ınstagram.com) nunca lo es. Aquí se revisa con
+// verificadores intercambiables y se guarda el veredicto un rato por URL.
+mod lista_negra;
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use tokio::sync::RwLock;
+
+pub use lista_negra::ListaNegra;
+
+// Cuánto confiamos en un veredicto antes de volver a preguntar
+const TTL_CACHE: Duration = Duration::from_secs(15 * 60);
+const MAX_CACHE: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Veredicto {
+    Seguro,
+    // Con el motivo, listo para mostrar al admin
+    Bloqueado(String),
+}
+
+/// Un verificador de URLs. Hoy solo existe la lista negra local, pero uno remoto
+/// (Safe Browsing, etc.) entra implementando esto.
+#[async_trait::async_trait]
+pub trait VerificadorUrl: Send + Sync {
+    fn nombre(&self) -> &'static str;
+
+    async fn revisar(&self, url: &url::Url) -> Veredicto;
+
+    /// Vuelve a leer sus fuentes (la revisión periódica lo llama antes de empezar)
+    async fn recargar(&self) {}
+}
+
+pub struct Seguridad {
+    verificadores: Vec<Box<dyn VerificadorUrl>>,
+    // Reputación por URL: (veredicto, cuándo se obtuvo)
+    cache: RwLock<HashMap<String, (Veredicto, Instant)>>,
+}
+
+impl Seguridad {
+    pub fn new(verificadores: Vec<Box<dyn VerificadorUrl>>) -> Self {
+        Seguridad { verificadores, cache: RwLock::new(HashMap::new()) }
+    }
+
+    /// Los verificadores configurados por variables de entorno
+    pub fn desde_env() -> Self {
+        let ruta = std::env::var("LISTA_NEGRA_PATH").unwrap_or_else(|_| "lista_negra.txt".to_string());
+        Seguridad::new(vec![Box::new(ListaNegra::desde_archivo(ruta))])
+    }
+
+    /// Revisa una URL con todos los verificadores; el primero que la bloquee gana
+    pub async fn revisar(&self, url: &str) -> Veredicto {
+        if let Some((veredicto, at)) = self.cache.read().await.get(url)
+            && at.elapsed() < TTL_CACHE
+        {
+            return veredicto.clone();
+        }
+
+        let veredicto = match url::Url::parse(url) {
+            Ok(parseada) => {
+                let mut veredicto = Veredicto::Seguro;
+                for verificador in &self.verificadores {
+                    if let Veredicto::Bloqueado(motivo) = verificador.revisar(&parseada).await {
+                        veredicto = Veredicto::Bloqueado(format!("{}: {}", verificador.nombre(), motivo));
+                        break;
+                    }
+                }
+                veredicto
+            }
+            Err(_) => Veredicto::Bloqueado("URL inválida".to_string()),
+        };
+
+        let mut cache = self.cache.write().await;
+        // Que no crezca sin límite entre recargas
+        if cache.len() >= MAX_CACHE {
+            cache.retain(|_, (_, at)| at.elapsed() < TTL_CACHE);
+        }
+        cache.insert(url.to_string(), (veredicto.clone(), Instant::now()));
+        veredicto
+    }
+
+    /// Revisa varias URLs (ej. todos los links de una landing). Regresa la primera bloqueada.
+    pub async fn primera_bloqueada<'a>(&self, urls: impl IntoIterator<Item = &'a str>) -> Option<(&'a str, String)> {
+        for url in urls {
+            if let Veredicto::Bloqueado(motivo) = self.revisar(url).await {
+                return Some((url, motivo));
+            }
+        }
+        None
+    }
+
+    /// Relee las fuentes y olvida los veredictos viejos
+    pub async fn recargar(&self) {
+        for verificador in &self.verificadores {
+            verificador.recargar().await;
+        }
+        self.cache.write().await.clear();
+    }
+}